@@ -0,0 +1,1609 @@
+//! Tokenizer and parser for the subset of the Brewfile DSL this installer
+//! understands. Every line that cannot be turned into an entry produces a
+//! [`Diagnostic`] instead of being silently dropped.
+
+use std::collections::HashSet;
+use std::fmt;
+
+mod condition;
+mod document;
+
+pub use condition::Host;
+pub use document::Document;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EntryKind {
+    Tap,
+    Brew,
+    Cask,
+    Mas,
+    Vscode,
+    Go,
+}
+
+impl EntryKind {
+    pub fn from_keyword(word: &str) -> Option<Self> {
+        match word {
+            "tap" => Some(EntryKind::Tap),
+            "brew" => Some(EntryKind::Brew),
+            "cask" => Some(EntryKind::Cask),
+            "mas" => Some(EntryKind::Mas),
+            "vscode" => Some(EntryKind::Vscode),
+            "go" => Some(EntryKind::Go),
+            _ => None,
+        }
+    }
+
+    /// Options this installer acts on; anything else is reported and ignored.
+    fn supported_options(self) -> &'static [&'static str] {
+        match self {
+            EntryKind::Tap => &[],
+            EntryKind::Brew => &["args", "link", "restart_service", "start_service"],
+            EntryKind::Cask => &["args", "greedy"],
+            EntryKind::Mas => &["id"],
+            EntryKind::Vscode => &[],
+            EntryKind::Go => &[],
+        }
+    }
+
+    /// Key used to detect duplicate entries of the same kind.
+    fn identity(self, entry: &Entry) -> String {
+        match self {
+            EntryKind::Mas => entry
+                .option("id")
+                .and_then(OptionValue::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            EntryKind::Tap | EntryKind::Vscode => entry.name.to_ascii_lowercase(),
+            // The same module pinned to two versions is still one binary.
+            EntryKind::Go => entry
+                .name
+                .rsplit_once('@')
+                .map_or(entry.name.as_str(), |(path, _)| path)
+                .to_string(),
+            _ => entry.name.clone(),
+        }
+    }
+
+    pub fn keyword(self) -> &'static str {
+        match self {
+            EntryKind::Tap => "tap",
+            EntryKind::Brew => "brew",
+            EntryKind::Cask => "cask",
+            EntryKind::Mas => "mas",
+            EntryKind::Vscode => "vscode",
+            EntryKind::Go => "go",
+        }
+    }
+}
+
+/// A single `kind "name", key: value, ...` line.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub kind: EntryKind,
+    pub name: String,
+    /// Extra positional strings, such as the clone URL of a `tap`.
+    pub extra: Vec<String>,
+    pub options: Vec<(String, OptionValue)>,
+    /// Text of the comment block directly above the entry, if any.
+    pub description: Option<String>,
+    /// Line of the comment directly above the entry that ends its
+    /// description.
+    pub description_line: Option<usize>,
+    /// Title of the section-header comment the entry falls under, such as
+    /// `Dev tools` for `# ---- Dev tools ----`.
+    pub category: Option<String>,
+    /// Why the entry does not apply to this host, when a surrounding or
+    /// trailing `if`/`unless` rules it out.
+    pub excluded: Option<String>,
+    pub line: usize,
+    /// Whether the entry sits inside an `if`/`unless` block.
+    pub nested: bool,
+}
+
+impl Entry {
+    pub fn option(&self, key: &str) -> Option<&OptionValue> {
+        self.options
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OptionValue {
+    Str(String),
+    Symbol(String),
+    Bool(bool),
+    Number(String),
+    Nil,
+    Array(Vec<OptionValue>),
+    Hash(Vec<(String, OptionValue)>),
+}
+
+impl OptionValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            OptionValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            OptionValue::Str(value) | OptionValue::Symbol(value) | OptionValue::Number(value) => {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Renders the value as Ruby source, as it would appear in a Brewfile.
+    pub fn to_ruby(&self) -> String {
+        match self {
+            OptionValue::Str(text) => quote(text),
+            OptionValue::Symbol(word) => format!(":{word}"),
+            OptionValue::Bool(value) => value.to_string(),
+            OptionValue::Number(text) => text.clone(),
+            OptionValue::Nil => "nil".to_string(),
+            OptionValue::Array(values) => format!(
+                "[{}]",
+                values
+                    .iter()
+                    .map(OptionValue::to_ruby)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            OptionValue::Hash(pairs) => format!("{{ {} }}", render_pairs(pairs)),
+        }
+    }
+
+    /// Translates `args:` into brew flags the way `brew bundle` does:
+    /// `["HEAD"]` becomes `--HEAD`, `{ appdir: "~/Apps" }` becomes
+    /// `--appdir=~/Apps`.
+    pub fn as_cli_args(&self) -> Vec<String> {
+        let flag = |name: &str| {
+            if name.starts_with('-') {
+                name.to_string()
+            } else {
+                format!("--{name}")
+            }
+        };
+        match self {
+            OptionValue::Array(values) => values
+                .iter()
+                .filter_map(|value| value.as_str().map(flag))
+                .collect(),
+            OptionValue::Hash(pairs) => pairs
+                .iter()
+                .filter_map(|(key, value)| match value {
+                    OptionValue::Bool(true) => Some(flag(key)),
+                    OptionValue::Bool(false) | OptionValue::Nil => None,
+                    other => other.as_str().map(|value| format!("{}={value}", flag(key))),
+                })
+                .collect(),
+            other => other.as_str().map(flag).into_iter().collect(),
+        }
+    }
+}
+
+/// Exclusion reason for entries under a condition the evaluator does not
+/// support. Such entries may well apply to this host.
+pub const UNEVALUATED: &str = "condition could not be evaluated";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The whole line was dropped.
+    Ignored,
+    /// The entry was kept, but part of it was not understood.
+    Warning,
+}
+
+/// Formats an entry line such as `mas "Xcode", id: 497799835`.
+pub fn format_entry(kind: EntryKind, name: &str, options: &[(String, OptionValue)]) -> String {
+    let mut line = format!("{} {}", kind.keyword(), quote(name));
+    if !options.is_empty() {
+        line.push_str(", ");
+        line.push_str(&render_pairs(options));
+    }
+    line
+}
+
+fn render_pairs(pairs: &[(String, OptionValue)]) -> String {
+    pairs
+        .iter()
+        .map(|(key, value)| format!("{key}: {}", value.to_ruby()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A problem found while reading the Brewfile. Lines and columns are 1-based.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+    pub source: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Brewfile {
+    pub entries: Vec<Entry>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+pub fn parse(contents: &str, host: &Host) -> Brewfile {
+    let mut brewfile = Brewfile::default();
+    // Lines of the comment block above the current line.
+    let mut pending_comment: Vec<String> = Vec::new();
+    // Whether a blank line (or the start of the file) came before that block.
+    let mut comment_set_apart = false;
+    let mut last_comment_line = 0;
+    let mut previous_blank = true;
+    let mut category: Option<String> = None;
+    let mut seen = HashSet::new();
+    let mut blocks: Vec<Block> = Vec::new();
+
+    for (index, source) in contents.lines().enumerate() {
+        let line = index + 1;
+        let after_blank = std::mem::replace(&mut previous_blank, source.trim().is_empty());
+        let mut tokens = match tokenize(source) {
+            Ok(tokens) => tokens,
+            Err(err) => {
+                brewfile.diagnostics.push(err.at(line, source));
+                pending_comment.clear();
+                continue;
+            }
+        };
+
+        if let [
+            Token {
+                kind: TokenKind::Comment(text),
+                ..
+            },
+        ] = tokens.as_slice()
+        {
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            if let Some(title) = section_heading(text) {
+                if !title.is_empty() {
+                    category = Some(title);
+                }
+                pending_comment.clear();
+                continue;
+            }
+            if commented_entry(text) {
+                // A commented-out entry describes nothing below it.
+                pending_comment.clear();
+                continue;
+            }
+            if pending_comment.is_empty() {
+                comment_set_apart = after_blank;
+            }
+            pending_comment.push(text.to_string());
+            last_comment_line = line;
+            continue;
+        }
+
+        // A trailing comment is not part of the statement.
+        if matches!(
+            tokens.last(),
+            Some(Token {
+                kind: TokenKind::Comment(_),
+                ..
+            })
+        ) {
+            tokens.pop();
+        }
+        if tokens.is_empty() {
+            // A lone short comment with a blank line on either side titles
+            // the entries below it.
+            if let [text] = pending_comment.as_slice()
+                && comment_set_apart
+                && looks_like_title(text)
+            {
+                category = Some(text.clone());
+            }
+            pending_comment.clear();
+            continue;
+        }
+
+        if let Some(result) = parse_block_line(&tokens, line, source, host, &mut blocks) {
+            if let Err(err) = result {
+                brewfile.diagnostics.push(err);
+            }
+            pending_comment.clear();
+            continue;
+        }
+
+        let description_line = (!pending_comment.is_empty()).then_some(last_comment_line);
+        let description =
+            (!pending_comment.is_empty()).then(|| std::mem::take(&mut pending_comment).join(" "));
+        let (statement, postfix) = split_postfix_condition(&tokens);
+        let mut entry = match parse_entry(statement) {
+            Ok(entry) => entry,
+            Err(err) => {
+                brewfile.diagnostics.push(err.at(line, source));
+                continue;
+            }
+        };
+
+        entry.line = line;
+        entry.nested = !blocks.is_empty();
+        entry.excluded = blocks.iter().find_map(|block| block.exclusion.clone());
+        if let Some((keyword, condition)) = postfix {
+            match evaluate_branch(keyword, condition, source, host) {
+                Ok(exclusion) => {
+                    if entry.excluded.is_none() {
+                        entry.excluded = exclusion;
+                    }
+                }
+                Err(err) => {
+                    brewfile.diagnostics.push(err.warn(line, source));
+                    if entry.excluded.is_none() {
+                        entry.excluded = Some(UNEVALUATED.to_string());
+                    }
+                }
+            }
+        }
+
+        // Excluded entries never clash with the ones that apply, so an
+        // `if`/`else` pair may list the same package twice.
+        let mut included_seen = HashSet::new();
+        let seen = if entry.excluded.is_some() {
+            &mut included_seen
+        } else {
+            &mut seen
+        };
+        match check_entry(&entry, statement, seen) {
+            Ok(warnings) => {
+                brewfile
+                    .diagnostics
+                    .extend(warnings.into_iter().map(|err| err.warn(line, source)));
+                entry.description = description;
+                entry.description_line = description_line;
+                entry.category = category.clone();
+                brewfile.entries.push(entry);
+            }
+            Err(err) => brewfile.diagnostics.push(err.at(line, source)),
+        }
+    }
+
+    for block in blocks {
+        brewfile.diagnostics.push(
+            ParseError::new(
+                block.column,
+                format!("`{}` is never closed with `end`", block.keyword),
+            )
+            .warn(block.line, &block.source),
+        );
+    }
+
+    brewfile
+}
+
+/// An open `if`/`unless` block.
+struct Block {
+    keyword: &'static str,
+    line: usize,
+    column: usize,
+    source: String,
+    /// Whether any branch of the block has matched so far.
+    taken: bool,
+    seen_else: bool,
+    /// Why entries in the current branch do not apply, if they don't.
+    exclusion: Option<String>,
+}
+
+/// Handles `if`, `unless`, `elsif`, `else` and `end` lines. Returns `None`
+/// when the line is not a block statement.
+fn parse_block_line(
+    tokens: &[Token],
+    line: usize,
+    source: &str,
+    host: &Host,
+    blocks: &mut Vec<Block>,
+) -> Option<Result<(), Diagnostic>> {
+    let Token {
+        kind: TokenKind::Ident(word),
+        column,
+        ..
+    } = &tokens[0]
+    else {
+        return None;
+    };
+    let column = *column;
+    let condition = &tokens[1..];
+    let no_arguments = |keyword: &str| {
+        if condition.is_empty() {
+            Ok(())
+        } else {
+            Err(ParseError::new(
+                condition[0].column,
+                format!("unexpected {} after `{keyword}`", condition[0].kind),
+            )
+            .at(line, source))
+        }
+    };
+
+    let result = match word.as_str() {
+        keyword @ ("if" | "unless") => {
+            let keyword = if keyword == "if" { "if" } else { "unless" };
+            let (taken, exclusion, result) = match evaluate_branch(keyword, condition, source, host)
+            {
+                Ok(exclusion) => (exclusion.is_none(), exclusion, Ok(())),
+                Err(err) => (
+                    false,
+                    Some(UNEVALUATED.to_string()),
+                    Err(err.warn(line, source)),
+                ),
+            };
+            blocks.push(Block {
+                keyword,
+                line,
+                column,
+                source: source.to_string(),
+                taken,
+                seen_else: false,
+                exclusion,
+            });
+            result
+        }
+        "elsif" => {
+            let Some(block) = blocks
+                .last_mut()
+                .filter(|block| block.keyword == "if" && !block.seen_else)
+            else {
+                return Some(Err(ParseError::new(
+                    column,
+                    "`elsif` without a matching `if`",
+                )
+                .at(line, source)));
+            };
+            if block.taken {
+                block.exclusion = Some(earlier_branch(block));
+                Ok(())
+            } else {
+                match evaluate_branch("if", condition, source, host) {
+                    Ok(exclusion) => {
+                        block.taken = exclusion.is_none();
+                        block.exclusion = exclusion;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        block.exclusion = Some(UNEVALUATED.to_string());
+                        Err(err.warn(line, source))
+                    }
+                }
+            }
+        }
+        "else" => {
+            let Some(block) = blocks.last_mut().filter(|block| !block.seen_else) else {
+                return Some(Err(ParseError::new(
+                    column,
+                    "`else` without a matching `if`",
+                )
+                .at(line, source)));
+            };
+            block.seen_else = true;
+            block.exclusion = block.taken.then(|| earlier_branch(block));
+            block.taken = true;
+            no_arguments("else")
+        }
+        "end" => {
+            if blocks.pop().is_none() {
+                return Some(Err(ParseError::new(
+                    column,
+                    "`end` without a matching `if`",
+                )
+                .at(line, source)));
+            }
+            no_arguments("end")
+        }
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn earlier_branch(block: &Block) -> String {
+    format!(
+        "an earlier branch of the `{}` on line {} applies",
+        block.keyword, block.line
+    )
+}
+
+/// Evaluates the condition of an `if`/`unless`. Returns why the guarded
+/// entries do not apply to this host, or `None` when they do.
+fn evaluate_branch(
+    keyword: &str,
+    condition: &[Token],
+    source: &str,
+    host: &Host,
+) -> Result<Option<String>, ParseError> {
+    let holds = condition::evaluate(condition, host)?;
+    let text = condition_text(condition, source);
+    Ok(match (keyword, holds) {
+        ("unless", true) => Some(format!("`{text}` is true")),
+        ("unless", false) => None,
+        (_, true) => None,
+        (_, false) => Some(format!("`{text}` is false")),
+    })
+}
+
+/// Source text spanned by the condition tokens, for use in messages.
+fn condition_text(condition: &[Token], source: &str) -> String {
+    let (Some(first), Some(last)) = (condition.first(), condition.last()) else {
+        return String::new();
+    };
+    source
+        .chars()
+        .skip(first.column - 1)
+        .take(last.end - first.column)
+        .collect()
+}
+
+/// Splits `brew "x" if OS.mac?` into the entry and its trailing modifier.
+fn split_postfix_condition(tokens: &[Token]) -> (&[Token], Option<(&'static str, &[Token])>) {
+    for (index, token) in tokens.iter().enumerate().skip(1) {
+        if let TokenKind::Ident(word) = &token.kind {
+            let keyword = match word.as_str() {
+                "if" => "if",
+                "unless" => "unless",
+                _ => continue,
+            };
+            return (&tokens[..index], Some((keyword, &tokens[index + 1..])));
+        }
+    }
+    (tokens, None)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Ident(String),
+    /// `key:` in Ruby's label hash syntax.
+    Label(String),
+    Str(String),
+    Symbol(String),
+    Number(String),
+    Comment(String),
+    Comma,
+    Arrow,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Punct(&'static str),
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Ident(word) => write!(f, "`{word}`"),
+            TokenKind::Label(word) => write!(f, "`{word}:`"),
+            TokenKind::Str(text) => write!(f, "string {text:?}"),
+            TokenKind::Symbol(word) => write!(f, "`:{word}`"),
+            TokenKind::Number(text) => write!(f, "`{text}`"),
+            TokenKind::Comment(_) => write!(f, "comment"),
+            TokenKind::Comma => write!(f, "`,`"),
+            TokenKind::Arrow => write!(f, "`=>`"),
+            TokenKind::LBracket => write!(f, "`[`"),
+            TokenKind::RBracket => write!(f, "`]`"),
+            TokenKind::LBrace => write!(f, "`{{`"),
+            TokenKind::RBrace => write!(f, "`}}`"),
+            TokenKind::Punct(text) => write!(f, "`{text}`"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    column: usize,
+    /// Column just past the last character of the token.
+    end: usize,
+}
+
+struct ParseError {
+    column: usize,
+    message: String,
+}
+
+impl ParseError {
+    fn new(column: usize, message: impl Into<String>) -> Self {
+        ParseError {
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn at(self, line: usize, source: &str) -> Diagnostic {
+        self.into_diagnostic(line, source, Severity::Ignored)
+    }
+
+    fn warn(self, line: usize, source: &str) -> Diagnostic {
+        self.into_diagnostic(line, source, Severity::Warning)
+    }
+
+    fn into_diagnostic(self, line: usize, source: &str, severity: Severity) -> Diagnostic {
+        Diagnostic {
+            line,
+            column: self.column,
+            severity,
+            message: self.message,
+            source: source.to_string(),
+        }
+    }
+}
+
+fn is_ident_start(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || ch == '_'
+}
+
+fn is_ident_continue(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_'
+}
+
+/// Recognises a section-header comment: text set off by a run of three or
+/// more `-`, `=`, `*`, `~`, `_` or `#` characters (`---- Dev tools ----`),
+/// by runs of two on both sides (`== Dev ==`), or a Markdown-style
+/// `## Dev tools`. Returns the title, which is empty for a plain rule such
+/// as `# ==========`. Comment text comes without its first `#`.
+fn section_heading(text: &str) -> Option<String> {
+    let decoration = |ch: char| matches!(ch, '-' | '=' | '*' | '~' | '_' | '#');
+    let title = |text: &str| {
+        text.trim_matches(|ch: char| decoration(ch) || ch.is_whitespace())
+            .to_string()
+    };
+    if text.starts_with('#') {
+        return Some(title(text));
+    }
+    let leading = text.chars().take_while(|&ch| decoration(ch)).count();
+    let trailing = text.chars().rev().take_while(|&ch| decoration(ch)).count();
+    (leading >= 3 || trailing >= 3 || (leading >= 2 && trailing >= 2)).then(|| title(text))
+}
+
+/// Whether a lone comment reads as a heading rather than prose.
+fn looks_like_title(text: &str) -> bool {
+    text.chars().count() <= 40 && !text.ends_with('.')
+}
+
+/// Whether comment text is a commented-out entry such as `brew "wget"`.
+fn commented_entry(text: &str) -> bool {
+    text.split_whitespace()
+        .next()
+        .is_some_and(|word| EntryKind::from_keyword(word).is_some())
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let ch = chars[pos];
+        let column = pos + 1;
+        if ch.is_whitespace() {
+            pos += 1;
+            continue;
+        }
+
+        let kind = match ch {
+            '#' => {
+                let text: String = chars[pos + 1..].iter().collect();
+                pos = chars.len();
+                TokenKind::Comment(text)
+            }
+            '"' | '\'' => {
+                let (text, next) = read_string(&chars, pos)?;
+                pos = next;
+                TokenKind::Str(text)
+            }
+            ':' if chars.get(pos + 1) == Some(&':') => {
+                pos += 2;
+                TokenKind::Punct("::")
+            }
+            ':' => match chars.get(pos + 1) {
+                Some(&next) if is_ident_start(next) => {
+                    let end = scan_ident(&chars, pos + 1);
+                    let word: String = chars[pos + 1..end].iter().collect();
+                    pos = end;
+                    TokenKind::Symbol(word)
+                }
+                Some('"') | Some('\'') => {
+                    let (text, next) = read_string(&chars, pos + 1)?;
+                    pos = next;
+                    TokenKind::Symbol(text)
+                }
+                _ => return Err(ParseError::new(column, "unexpected `:`")),
+            },
+            ch if is_ident_start(ch) => {
+                let end = scan_ident(&chars, pos);
+                let word: String = chars[pos..end].iter().collect();
+                let is_label = chars.get(end) == Some(&':') && chars.get(end + 1) != Some(&':');
+                if is_label {
+                    pos = end + 1;
+                    TokenKind::Label(word)
+                } else {
+                    pos = end;
+                    TokenKind::Ident(word)
+                }
+            }
+            ch if ch.is_ascii_digit() || (ch == '-' && next_is_digit(&chars, pos)) => {
+                let start = pos;
+                pos += 1;
+                while pos < chars.len()
+                    && (chars[pos].is_ascii_digit() || chars[pos] == '_' || chars[pos] == '.')
+                {
+                    pos += 1;
+                }
+                TokenKind::Number(chars[start..pos].iter().filter(|&&c| c != '_').collect())
+            }
+            ',' => {
+                pos += 1;
+                TokenKind::Comma
+            }
+            '[' => {
+                pos += 1;
+                TokenKind::LBracket
+            }
+            ']' => {
+                pos += 1;
+                TokenKind::RBracket
+            }
+            '{' => {
+                pos += 1;
+                TokenKind::LBrace
+            }
+            '}' => {
+                pos += 1;
+                TokenKind::RBrace
+            }
+            _ => {
+                let rest: String = chars[pos..chars.len().min(pos + 2)].iter().collect();
+                let punct = ["=>", "==", "!=", "&&", "||", ">=", "<="]
+                    .into_iter()
+                    .find(|op| rest == *op)
+                    .or_else(|| {
+                        ["!", ".", "(", ")", ">", "<"]
+                            .into_iter()
+                            .find(|op| rest.starts_with(op))
+                    });
+                match punct {
+                    Some("=>") => {
+                        pos += 2;
+                        TokenKind::Arrow
+                    }
+                    Some(op) => {
+                        pos += op.len();
+                        TokenKind::Punct(op)
+                    }
+                    None => {
+                        return Err(ParseError::new(
+                            column,
+                            format!("unexpected character `{ch}`"),
+                        ));
+                    }
+                }
+            }
+        };
+        tokens.push(Token {
+            kind,
+            column,
+            end: pos + 1,
+        });
+    }
+
+    Ok(tokens)
+}
+
+fn next_is_digit(chars: &[char], pos: usize) -> bool {
+    chars.get(pos + 1).is_some_and(|ch| ch.is_ascii_digit())
+}
+
+/// Scans an identifier starting at `pos`, including Ruby's trailing `?`/`!`
+/// on method names (but not the `!` of a following `!=`).
+fn scan_ident(chars: &[char], pos: usize) -> usize {
+    let mut end = pos;
+    while end < chars.len() && is_ident_continue(chars[end]) {
+        end += 1;
+    }
+    if matches!(chars.get(end), Some('?') | Some('!')) && chars.get(end + 1) != Some(&'=') {
+        end += 1;
+    }
+    end
+}
+
+/// Reads a single- or double-quoted Ruby string starting at `start`.
+/// Returns the unescaped text and the position just past the closing quote.
+fn read_string(chars: &[char], start: usize) -> Result<(String, usize), ParseError> {
+    let quote = chars[start];
+    let mut text = String::new();
+    let mut pos = start + 1;
+    while pos < chars.len() {
+        match chars[pos] {
+            '\\' if pos + 1 < chars.len() => {
+                let escaped = chars[pos + 1];
+                match (quote, escaped) {
+                    ('"', 'n') => text.push('\n'),
+                    ('"', 't') => text.push('\t'),
+                    (_, ch) if ch == quote || ch == '\\' => text.push(ch),
+                    (_, ch) => {
+                        text.push('\\');
+                        text.push(ch);
+                    }
+                }
+                pos += 2;
+            }
+            ch if ch == quote => return Ok((text, pos + 1)),
+            ch => {
+                text.push(ch);
+                pos += 1;
+            }
+        }
+    }
+    Err(ParseError::new(start + 1, "unterminated string"))
+}
+
+struct TokenStream<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    end_column: usize,
+}
+
+impl<'a> TokenStream<'a> {
+    fn new(tokens: &'a [Token]) -> Self {
+        // A trailing comment is not part of the statement.
+        let tokens = match tokens.last() {
+            Some(Token {
+                kind: TokenKind::Comment(_),
+                ..
+            }) => &tokens[..tokens.len() - 1],
+            _ => tokens,
+        };
+        let end_column = tokens.last().map_or(1, |token| token.end);
+        TokenStream {
+            tokens,
+            pos: 0,
+            end_column,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.peek().is_some_and(|token| token.kind == *kind) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn column(&self) -> usize {
+        self.peek().map_or(self.end_column, |token| token.column)
+    }
+
+    fn unexpected(&self, expected: &str) -> ParseError {
+        match self.peek() {
+            Some(token) => ParseError::new(
+                token.column,
+                format!("expected {expected}, found {}", token.kind),
+            ),
+            None => ParseError::new(self.end_column, format!("expected {expected}")),
+        }
+    }
+}
+
+fn parse_entry(tokens: &[Token]) -> Result<Entry, ParseError> {
+    let mut stream = TokenStream::new(tokens);
+    let keyword = stream.next().ok_or_else(|| stream.unexpected("an entry"))?;
+    let kind = match &keyword.kind {
+        TokenKind::Ident(word) => EntryKind::from_keyword(word).ok_or_else(|| {
+            ParseError::new(
+                keyword.column,
+                format!("unsupported directive `{word}`; line ignored"),
+            )
+        })?,
+        other => {
+            return Err(ParseError::new(
+                keyword.column,
+                format!("expected an entry, found {other}"),
+            ));
+        }
+    };
+
+    let name = match stream.next() {
+        Some(Token {
+            kind: TokenKind::Str(name),
+            column,
+            ..
+        }) => {
+            if name.trim().is_empty() {
+                return Err(ParseError::new(*column, "entry name is empty"));
+            }
+            name.clone()
+        }
+        _ => {
+            stream.pos -= 1;
+            return Err(stream.unexpected(&format!("a quoted name after `{}`", kind.keyword())));
+        }
+    };
+
+    let mut entry = Entry {
+        kind,
+        name,
+        extra: Vec::new(),
+        options: Vec::new(),
+        description: None,
+        description_line: None,
+        category: None,
+        excluded: None,
+        line: 0,
+        nested: false,
+    };
+
+    while stream.peek().is_some() {
+        if !stream.eat(&TokenKind::Comma) {
+            return Err(stream.unexpected("`,` or end of line"));
+        }
+        match stream.peek().map(|token| &token.kind) {
+            Some(TokenKind::Str(text)) if entry.options.is_empty() && is_positional(&stream) => {
+                entry.extra.push(text.clone());
+                stream.next();
+            }
+            _ => {
+                let pair = parse_pair(&mut stream)?;
+                entry.options.push(pair);
+            }
+        }
+    }
+
+    Ok(entry)
+}
+
+/// Validates a parsed entry. Returns warnings for parts that were ignored,
+/// or an error when the entry as a whole cannot be used.
+fn check_entry(
+    entry: &Entry,
+    tokens: &[Token],
+    seen: &mut HashSet<(EntryKind, String)>,
+) -> Result<Vec<ParseError>, ParseError> {
+    let option_column = |key: &str| {
+        tokens
+            .iter()
+            .find(|token| match &token.kind {
+                TokenKind::Label(word) | TokenKind::Symbol(word) | TokenKind::Str(word) => {
+                    word == key
+                }
+                _ => false,
+            })
+            .map_or(1, |token| token.column)
+    };
+
+    if entry.kind == EntryKind::Mas {
+        let valid_id = entry
+            .option("id")
+            .and_then(OptionValue::as_str)
+            .is_some_and(|id| !id.is_empty() && id.chars().all(|ch| ch.is_ascii_digit()));
+        if !valid_id {
+            let column = tokens.last().map_or(1, |token| token.column);
+            return Err(ParseError::new(
+                column,
+                "`mas` entry needs a numeric `id:`; line ignored",
+            ));
+        }
+    }
+
+    if !seen.insert((entry.kind, entry.kind.identity(entry))) {
+        return Err(ParseError::new(
+            1,
+            format!(
+                "duplicate {} entry \"{}\"; line ignored",
+                entry.kind.keyword(),
+                entry.name
+            ),
+        ));
+    }
+
+    let mut warnings = Vec::new();
+    let supported = entry.kind.supported_options();
+    for (key, _) in &entry.options {
+        if !supported.contains(&key.as_str()) {
+            warnings.push(ParseError::new(
+                option_column(key),
+                format!(
+                    "option `{key}` is not supported for `{}` entries and was ignored",
+                    entry.kind.keyword()
+                ),
+            ));
+        }
+    }
+    if entry.kind != EntryKind::Tap && !entry.extra.is_empty() {
+        warnings.push(ParseError::new(
+            1,
+            format!("extra arguments to `{}` were ignored", entry.kind.keyword()),
+        ));
+    }
+    Ok(warnings)
+}
+
+/// A string is positional unless it is the key of a `"key" => value` pair.
+fn is_positional(stream: &TokenStream) -> bool {
+    stream
+        .tokens
+        .get(stream.pos + 1)
+        .is_none_or(|token| token.kind != TokenKind::Arrow)
+}
+
+/// Parses `key: value`, `:key => value` or `"key" => value`.
+fn parse_pair(stream: &mut TokenStream) -> Result<(String, OptionValue), ParseError> {
+    let key = match stream.next().map(|token| &token.kind) {
+        Some(TokenKind::Label(key)) => return Ok((key.clone(), parse_value(stream)?)),
+        Some(TokenKind::Symbol(key)) | Some(TokenKind::Str(key)) => key.clone(),
+        _ => {
+            stream.pos -= 1;
+            return Err(stream.unexpected("an option such as `key: value`"));
+        }
+    };
+    if !stream.eat(&TokenKind::Arrow) {
+        return Err(stream.unexpected("`=>`"));
+    }
+    Ok((key, parse_value(stream)?))
+}
+
+fn parse_value(stream: &mut TokenStream) -> Result<OptionValue, ParseError> {
+    let column = stream.column();
+    let Some(token) = stream.next() else {
+        return Err(ParseError::new(column, "expected a value"));
+    };
+    match &token.kind {
+        TokenKind::Str(text) => Ok(OptionValue::Str(text.clone())),
+        TokenKind::Symbol(word) => Ok(OptionValue::Symbol(word.clone())),
+        TokenKind::Number(text) => Ok(OptionValue::Number(text.clone())),
+        TokenKind::Ident(word) if word == "true" => Ok(OptionValue::Bool(true)),
+        TokenKind::Ident(word) if word == "false" => Ok(OptionValue::Bool(false)),
+        TokenKind::Ident(word) if word == "nil" => Ok(OptionValue::Nil),
+        TokenKind::LBracket => {
+            let mut values = Vec::new();
+            loop {
+                if stream.eat(&TokenKind::RBracket) {
+                    return Ok(OptionValue::Array(values));
+                }
+                if stream.peek().is_none() {
+                    return Err(stream.unexpected("`]`"));
+                }
+                values.push(parse_value(stream)?);
+                if !stream.eat(&TokenKind::Comma)
+                    && stream.peek().map(|t| &t.kind) != Some(&TokenKind::RBracket)
+                {
+                    return Err(stream.unexpected("`,` or `]`"));
+                }
+            }
+        }
+        TokenKind::LBrace => {
+            let mut pairs = Vec::new();
+            loop {
+                if stream.eat(&TokenKind::RBrace) {
+                    return Ok(OptionValue::Hash(pairs));
+                }
+                if stream.peek().is_none() {
+                    return Err(stream.unexpected("`}`"));
+                }
+                pairs.push(parse_pair(stream)?);
+                if !stream.eat(&TokenKind::Comma)
+                    && stream.peek().map(|t| &t.kind) != Some(&TokenKind::RBrace)
+                {
+                    return Err(stream.unexpected("`,` or `}`"));
+                }
+            }
+        }
+        other => Err(ParseError::new(
+            token.column,
+            format!("expected a value, found {other}"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: Host = Host {
+        os: "macos",
+        arch: "aarch64",
+    };
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        match tokenize(source) {
+            Ok(tokens) => tokens.into_iter().map(|token| token.kind).collect(),
+            Err(err) => panic!("{source:?} did not tokenize: {}", err.message),
+        }
+    }
+
+    fn tokenize_error(source: &str) -> (usize, String) {
+        match tokenize(source) {
+            Ok(tokens) => panic!("{source:?} tokenized as {tokens:?}"),
+            Err(err) => (err.column, err.message),
+        }
+    }
+
+    fn entry<'a>(brewfile: &'a Brewfile, name: &str) -> &'a Entry {
+        brewfile
+            .entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .unwrap_or_else(|| panic!("no entry named {name}"))
+    }
+
+    fn messages(brewfile: &Brewfile) -> Vec<(usize, Severity, &str)> {
+        brewfile
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.line,
+                    diagnostic.severity,
+                    diagnostic.message.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tokenizes_an_entry_with_options_and_a_trailing_comment() {
+        assert_eq!(
+            kinds(r#"brew "jq", args: ["HEAD"], :link => false # note"#),
+            vec![
+                TokenKind::Ident("brew".into()),
+                TokenKind::Str("jq".into()),
+                TokenKind::Comma,
+                TokenKind::Label("args".into()),
+                TokenKind::LBracket,
+                TokenKind::Str("HEAD".into()),
+                TokenKind::RBracket,
+                TokenKind::Comma,
+                TokenKind::Symbol("link".into()),
+                TokenKind::Arrow,
+                TokenKind::Ident("false".into()),
+                TokenKind::Comment(" note".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenizes_conditions() {
+        assert_eq!(
+            kinds(r#"Hardware::CPU.arm? && ENV["CI"] != nil"#),
+            vec![
+                TokenKind::Ident("Hardware".into()),
+                TokenKind::Punct("::"),
+                TokenKind::Ident("CPU".into()),
+                TokenKind::Punct("."),
+                TokenKind::Ident("arm?".into()),
+                TokenKind::Punct("&&"),
+                TokenKind::Ident("ENV".into()),
+                TokenKind::LBracket,
+                TokenKind::Str("CI".into()),
+                TokenKind::RBracket,
+                TokenKind::Punct("!="),
+                TokenKind::Ident("nil".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenizes_strings_numbers_and_symbols() {
+        assert_eq!(
+            kinds(r#"'it\'s' "a\"b\n" 1_000 -2 :"odd key""#),
+            vec![
+                TokenKind::Str("it's".into()),
+                TokenKind::Str("a\"b\n".into()),
+                TokenKind::Number("1000".into()),
+                TokenKind::Number("-2".into()),
+                TokenKind::Symbol("odd key".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_tokenizer_errors_with_columns() {
+        assert_eq!(
+            tokenize_error(r#"brew "jq"#),
+            (6, "unterminated string".to_string())
+        );
+        assert_eq!(
+            tokenize_error(r#"brew "jq"; cask "x""#),
+            (10, "unexpected character `;`".to_string())
+        );
+        assert_eq!(
+            tokenize_error("brew : x"),
+            (6, "unexpected `:`".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_entries_and_their_options() {
+        let brewfile = parse(
+            concat!(
+                "tap \"owner/repo\", \"https://example.com/repo.git\"\n",
+                "brew \"jq\", args: [\"HEAD\"], restart_service: :changed\n",
+                "cask \"firefox\", greedy: true\n",
+                "mas \"Xcode\", id: 497799835\n",
+                "vscode \"rust-lang.rust-analyzer\"\n",
+                "go \"golang.org/x/tools/gopls@latest\"\n",
+            ),
+            &MAC,
+        );
+        assert!(brewfile.diagnostics.is_empty());
+        let found: Vec<_> = brewfile
+            .entries
+            .iter()
+            .map(|entry| (entry.kind, entry.name.as_str(), entry.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (EntryKind::Tap, "owner/repo", 1),
+                (EntryKind::Brew, "jq", 2),
+                (EntryKind::Cask, "firefox", 3),
+                (EntryKind::Mas, "Xcode", 4),
+                (EntryKind::Vscode, "rust-lang.rust-analyzer", 5),
+                (EntryKind::Go, "golang.org/x/tools/gopls@latest", 6),
+            ]
+        );
+        assert_eq!(
+            entry(&brewfile, "owner/repo").extra,
+            vec!["https://example.com/repo.git"]
+        );
+        let jq = entry(&brewfile, "jq");
+        assert_eq!(
+            jq.option("args").map(OptionValue::as_cli_args),
+            Some(vec!["--HEAD".to_string()])
+        );
+        assert_eq!(
+            jq.option("restart_service").and_then(OptionValue::as_str),
+            Some("changed")
+        );
+        assert_eq!(
+            entry(&brewfile, "firefox")
+                .option("greedy")
+                .and_then(OptionValue::as_bool),
+            Some(true)
+        );
+        assert_eq!(
+            entry(&brewfile, "Xcode")
+                .option("id")
+                .and_then(OptionValue::as_str),
+            Some("497799835")
+        );
+    }
+
+    #[test]
+    fn treats_go_modules_at_different_versions_as_duplicates() {
+        let brewfile = parse(
+            concat!(
+                "go \"github.com/owner/tool@v1.2.0\"\n",
+                "go \"github.com/owner/tool@latest\"\n",
+                "go \"github.com/owner/tool\"\n",
+                "go \"github.com/owner/tool/v2@v2.0.0\"\n",
+            ),
+            &MAC,
+        );
+        assert_eq!(
+            messages(&brewfile),
+            vec![
+                (
+                    2,
+                    Severity::Ignored,
+                    "duplicate go entry \"github.com/owner/tool@latest\"; line ignored"
+                ),
+                (
+                    3,
+                    Severity::Ignored,
+                    "duplicate go entry \"github.com/owner/tool\"; line ignored"
+                ),
+            ]
+        );
+        assert_eq!(brewfile.entries.len(), 2);
+    }
+
+    #[test]
+    fn reports_lines_it_cannot_use() {
+        let brewfile = parse(
+            concat!(
+                "brew \"jq\"\n",
+                "cask_args appdir: \"~/Applications\"\n",
+                "mas \"Xcode\"\n",
+                "brew \"jq\"\n",
+                "brew jq\n",
+                "brew \"wget\", unknown: 1\n",
+                "cask \"x\", \"extra\"\n",
+                "brew \"fd\" \"rg\"\n",
+            ),
+            &MAC,
+        );
+        assert_eq!(
+            messages(&brewfile),
+            vec![
+                (
+                    2,
+                    Severity::Ignored,
+                    "unsupported directive `cask_args`; line ignored"
+                ),
+                (
+                    3,
+                    Severity::Ignored,
+                    "`mas` entry needs a numeric `id:`; line ignored"
+                ),
+                (
+                    4,
+                    Severity::Ignored,
+                    "duplicate brew entry \"jq\"; line ignored"
+                ),
+                (
+                    5,
+                    Severity::Ignored,
+                    "expected a quoted name after `brew`, found `jq`"
+                ),
+                (
+                    6,
+                    Severity::Warning,
+                    "option `unknown` is not supported for `brew` entries and was ignored"
+                ),
+                (
+                    7,
+                    Severity::Warning,
+                    "extra arguments to `cask` were ignored"
+                ),
+                (
+                    8,
+                    Severity::Ignored,
+                    "expected `,` or end of line, found string \"rg\""
+                ),
+            ]
+        );
+        let names: Vec<_> = brewfile
+            .entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["jq", "wget", "x"]);
+        assert_eq!(
+            brewfile.diagnostics[4].to_string(),
+            "6:14: option `unknown` is not supported for `brew` entries and was ignored"
+        );
+    }
+
+    #[test]
+    fn points_end_of_line_errors_past_the_last_token() {
+        let brewfile = parse("brew \"jq\", # trailing comment\nbrew\n", &MAC);
+        let found: Vec<_> = brewfile
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                "1:11: expected an option such as `key: value`",
+                "2:5: expected a quoted name after `brew`",
+            ]
+        );
+    }
+
+    #[test]
+    fn takes_one_branch_of_if_elsif_else() {
+        let brewfile = parse(
+            concat!(
+                "if OS.linux?\n",
+                "  brew \"linux-only\"\n",
+                "elsif Hardware::CPU.arm?\n",
+                "  brew \"arm-only\"\n",
+                "elsif OS.mac?\n",
+                "  brew \"mac-fallback\"\n",
+                "else\n",
+                "  brew \"other\"\n",
+                "end\n",
+                "brew \"everywhere\"\n",
+            ),
+            &MAC,
+        );
+        assert!(brewfile.diagnostics.is_empty());
+        assert_eq!(
+            entry(&brewfile, "linux-only").excluded.as_deref(),
+            Some("`OS.linux?` is false")
+        );
+        assert_eq!(entry(&brewfile, "arm-only").excluded, None);
+        assert!(entry(&brewfile, "arm-only").nested);
+        assert_eq!(
+            entry(&brewfile, "mac-fallback").excluded.as_deref(),
+            Some("an earlier branch of the `if` on line 1 applies")
+        );
+        assert_eq!(
+            entry(&brewfile, "other").excluded.as_deref(),
+            Some("an earlier branch of the `if` on line 1 applies")
+        );
+        let everywhere = entry(&brewfile, "everywhere");
+        assert_eq!(everywhere.excluded, None);
+        assert!(!everywhere.nested);
+    }
+
+    #[test]
+    fn applies_unless_and_postfix_conditions() {
+        let brewfile = parse(
+            concat!(
+                "unless OS.mac?\n",
+                "  brew \"not-on-mac\"\n",
+                "end\n",
+                "brew \"mac\" if OS.mac?\n",
+                "brew \"linux\" if OS.linux?\n",
+                "cask \"intel\" unless Hardware::CPU.arm?\n",
+            ),
+            &MAC,
+        );
+        assert!(brewfile.diagnostics.is_empty());
+        assert_eq!(
+            entry(&brewfile, "not-on-mac").excluded.as_deref(),
+            Some("`OS.mac?` is true")
+        );
+        assert_eq!(entry(&brewfile, "mac").excluded, None);
+        assert_eq!(
+            entry(&brewfile, "linux").excluded.as_deref(),
+            Some("`OS.linux?` is false")
+        );
+        assert_eq!(
+            entry(&brewfile, "intel").excluded.as_deref(),
+            Some("`Hardware::CPU.arm?` is true")
+        );
+    }
+
+    #[test]
+    fn keeps_entries_under_unsupported_conditions_with_a_warning() {
+        let brewfile = parse(
+            concat!(
+                "if MacOS.version >= :sonoma\n",
+                "  brew \"new\"\n",
+                "end\n",
+                "brew \"old\" if system(\"true\")\n",
+            ),
+            &MAC,
+        );
+        assert_eq!(
+            messages(&brewfile),
+            vec![
+                (1, Severity::Warning, "unsupported condition `MacOS`"),
+                (4, Severity::Warning, "unsupported condition `system`"),
+            ]
+        );
+        for name in ["new", "old"] {
+            assert_eq!(
+                entry(&brewfile, name).excluded.as_deref(),
+                Some(UNEVALUATED)
+            );
+        }
+    }
+
+    #[test]
+    fn reports_unbalanced_blocks() {
+        let brewfile = parse(
+            concat!(
+                "end\n",
+                "else\n",
+                "if OS.mac?\n",
+                "  brew \"jq\"\n",
+                "else OS.linux?\n",
+                "elsif OS.linux?\n",
+            ),
+            &MAC,
+        );
+        assert_eq!(
+            messages(&brewfile),
+            vec![
+                (1, Severity::Ignored, "`end` without a matching `if`"),
+                (2, Severity::Ignored, "`else` without a matching `if`"),
+                (5, Severity::Ignored, "unexpected `OS` after `else`"),
+                (6, Severity::Ignored, "`elsif` without a matching `if`"),
+                (3, Severity::Warning, "`if` is never closed with `end`"),
+            ]
+        );
+        assert_eq!(entry(&brewfile, "jq").excluded, None);
+    }
+
+    #[test]
+    fn lists_a_package_once_per_exclusive_branch() {
+        let brewfile = parse(
+            concat!(
+                "if OS.mac?\n",
+                "  cask \"docker\"\n",
+                "else\n",
+                "  cask \"docker\"\n",
+                "end\n",
+            ),
+            &MAC,
+        );
+        assert!(brewfile.diagnostics.is_empty());
+        assert_eq!(brewfile.entries.len(), 2);
+    }
+
+    #[test]
+    fn recognises_section_headings() {
+        let heading = |text: &str| section_heading(text);
+        assert_eq!(heading("---- Dev tools ----").as_deref(), Some("Dev tools"));
+        assert_eq!(heading("== Dev ==").as_deref(), Some("Dev"));
+        assert_eq!(heading("Fonts ~~~").as_deref(), Some("Fonts"));
+        assert_eq!(heading("# Browsers").as_deref(), Some("Browsers"));
+        assert_eq!(heading("==========").as_deref(), Some(""));
+        assert_eq!(heading("- a bullet"), None);
+        assert_eq!(heading("JSON processor"), None);
+    }
+
+    #[test]
+    fn recognises_titles_and_commented_out_entries() {
+        assert!(looks_like_title("Browsers"));
+        assert!(!looks_like_title("Installs the browsers we use."));
+        assert!(!looks_like_title(
+            "a rather long comment that goes on for more than forty characters"
+        ));
+        assert!(commented_entry(r#"brew "wget""#));
+        assert!(!commented_entry("brewing tools"));
+    }
+
+    #[test]
+    fn assigns_categories_and_descriptions() {
+        let brewfile = parse(
+            concat!(
+                "# ---- Dev ----\n",
+                "# JSON processor\n",
+                "brew \"jq\"\n",
+                "# brew \"old\"\n",
+                "brew \"wget\"\n",
+                "\n",
+                "# Browsers\n",
+                "\n",
+                "cask \"firefox\"\n",
+                "brew \"rosetta-tool\"\n",
+                "# needs rosetta\n",
+                "\n",
+                "cask \"intel-app\"\n",
+            ),
+            &MAC,
+        );
+        let summary = |name: &str| {
+            let entry = entry(&brewfile, name);
+            (
+                entry.category.as_deref(),
+                entry.description.as_deref(),
+                entry.description_line,
+            )
+        };
+        assert_eq!(
+            summary("jq"),
+            (Some("Dev"), Some("JSON processor"), Some(2))
+        );
+        assert_eq!(summary("wget"), (Some("Dev"), None, None));
+        assert_eq!(summary("firefox"), (Some("Browsers"), None, None));
+        // A note that is not set apart by a blank line above is no title.
+        assert_eq!(summary("intel-app"), (Some("Browsers"), None, None));
+    }
+}
@@ -0,0 +1,392 @@
+//! Evaluator for the small set of Ruby conditions that shared Brewfiles use
+//! to target particular hosts, such as `if OS.mac?` or
+//! `unless ENV["CI"].nil?`.
+
+use std::path::Path;
+
+use super::{ParseError, Token, TokenKind, TokenStream};
+
+/// Facts about the machine the Brewfile is being evaluated for.
+#[derive(Clone, Debug)]
+pub struct Host {
+    pub os: &'static str,
+    pub arch: &'static str,
+}
+
+impl Host {
+    pub fn current() -> Self {
+        Host {
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+        }
+    }
+
+    fn env(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Bool(bool),
+    Str(String),
+    Nil,
+}
+
+impl Value {
+    /// Ruby truthiness: only `nil` and `false` are falsy.
+    fn truthy(&self) -> bool {
+        !matches!(self, Value::Bool(false) | Value::Nil)
+    }
+}
+
+/// Evaluates a condition such as `OS.mac? && !ENV["CI"]`.
+pub fn evaluate(tokens: &[Token], host: &Host) -> Result<bool, ParseError> {
+    let mut stream = TokenStream::new(tokens);
+    if stream.peek().is_none() {
+        return Err(stream.unexpected("a condition"));
+    }
+    let value = or_expr(&mut stream, host)?;
+    if stream.peek().is_some() {
+        return Err(stream.unexpected("end of condition"));
+    }
+    Ok(value.truthy())
+}
+
+fn is_word(token: Option<&Token>, word: &str) -> bool {
+    matches!(token, Some(Token { kind: TokenKind::Ident(ident), .. }) if ident == word)
+}
+
+fn is_punct(token: Option<&Token>, punct: &str) -> bool {
+    matches!(token, Some(Token { kind: TokenKind::Punct(op), .. }) if *op == punct)
+}
+
+fn expect_punct(stream: &mut TokenStream, punct: &str) -> Result<(), ParseError> {
+    if is_punct(stream.peek(), punct) {
+        stream.next();
+        Ok(())
+    } else {
+        Err(stream.unexpected(&format!("`{punct}`")))
+    }
+}
+
+fn or_expr(stream: &mut TokenStream, host: &Host) -> Result<Value, ParseError> {
+    let mut value = and_expr(stream, host)?;
+    while is_punct(stream.peek(), "||") || is_word(stream.peek(), "or") {
+        stream.next();
+        let rhs = and_expr(stream, host)?;
+        if !value.truthy() {
+            value = rhs;
+        }
+    }
+    Ok(value)
+}
+
+fn and_expr(stream: &mut TokenStream, host: &Host) -> Result<Value, ParseError> {
+    let mut value = not_expr(stream, host)?;
+    while is_punct(stream.peek(), "&&") || is_word(stream.peek(), "and") {
+        stream.next();
+        let rhs = not_expr(stream, host)?;
+        if value.truthy() {
+            value = rhs;
+        }
+    }
+    Ok(value)
+}
+
+fn not_expr(stream: &mut TokenStream, host: &Host) -> Result<Value, ParseError> {
+    if is_punct(stream.peek(), "!") || is_word(stream.peek(), "not") {
+        stream.next();
+        let value = not_expr(stream, host)?;
+        return Ok(Value::Bool(!value.truthy()));
+    }
+    comparison(stream, host)
+}
+
+fn comparison(stream: &mut TokenStream, host: &Host) -> Result<Value, ParseError> {
+    let lhs = postfix(stream, host)?;
+    for (op, equal) in [("==", true), ("!=", false)] {
+        if is_punct(stream.peek(), op) {
+            stream.next();
+            let rhs = postfix(stream, host)?;
+            return Ok(Value::Bool((lhs == rhs) == equal));
+        }
+    }
+    Ok(lhs)
+}
+
+/// A primary value followed by `.nil?` or `.empty?`.
+fn postfix(stream: &mut TokenStream, host: &Host) -> Result<Value, ParseError> {
+    let mut value = primary(stream, host)?;
+    while is_punct(stream.peek(), ".") {
+        stream.next();
+        let method = method_name(stream)?;
+        value = match method.as_str() {
+            "nil?" => Value::Bool(value == Value::Nil),
+            "empty?" => Value::Bool(matches!(&value, Value::Str(text) if text.is_empty())),
+            other => return Err(unsupported(stream, other)),
+        };
+    }
+    Ok(value)
+}
+
+fn method_name(stream: &mut TokenStream) -> Result<String, ParseError> {
+    match stream.next().map(|token| &token.kind) {
+        Some(TokenKind::Ident(name)) => Ok(name.clone()),
+        _ => {
+            stream.pos -= 1;
+            Err(stream.unexpected("a method name"))
+        }
+    }
+}
+
+fn unsupported(stream: &TokenStream, what: &str) -> ParseError {
+    let column = stream
+        .tokens
+        .get(stream.pos.saturating_sub(1))
+        .map_or(stream.end_column, |token| token.column);
+    ParseError::new(column, format!("unsupported condition `{what}`"))
+}
+
+fn string_argument(stream: &mut TokenStream, host: &Host) -> Result<String, ParseError> {
+    expect_punct(stream, "(")?;
+    let value = or_expr(stream, host)?;
+    expect_punct(stream, ")")?;
+    match value {
+        Value::Str(text) => Ok(text),
+        _ => Err(stream.unexpected("a string argument")),
+    }
+}
+
+fn primary(stream: &mut TokenStream, host: &Host) -> Result<Value, ParseError> {
+    let column = stream.column();
+    let Some(token) = stream.next() else {
+        return Err(ParseError::new(column, "expected a condition"));
+    };
+    let word = match &token.kind {
+        TokenKind::Str(text) => return Ok(Value::Str(text.clone())),
+        TokenKind::Punct("(") => {
+            let value = or_expr(stream, host)?;
+            expect_punct(stream, ")")?;
+            return Ok(value);
+        }
+        TokenKind::Ident(word) => word.as_str(),
+        other => {
+            return Err(ParseError::new(
+                token.column,
+                format!("expected a condition, found {other}"),
+            ));
+        }
+    };
+
+    match word {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        "nil" => Ok(Value::Nil),
+        "OS" => {
+            expect_punct(stream, ".")?;
+            match method_name(stream)?.as_str() {
+                "mac?" => Ok(Value::Bool(host.os == "macos")),
+                "linux?" => Ok(Value::Bool(host.os == "linux")),
+                other => Err(unsupported(stream, &format!("OS.{other}"))),
+            }
+        }
+        "Hardware" => {
+            expect_punct(stream, "::")?;
+            if !is_word(stream.next(), "CPU") {
+                return Err(unsupported(stream, "Hardware::…"));
+            }
+            expect_punct(stream, ".")?;
+            match method_name(stream)?.as_str() {
+                "arm?" => Ok(Value::Bool(matches!(host.arch, "aarch64" | "arm"))),
+                "intel?" => Ok(Value::Bool(matches!(host.arch, "x86_64" | "x86"))),
+                other => Err(unsupported(stream, &format!("Hardware::CPU.{other}"))),
+            }
+        }
+        "ENV" => {
+            if stream.eat(&TokenKind::LBracket) {
+                let name = match stream.next().map(|token| &token.kind) {
+                    Some(TokenKind::Str(name)) => name.clone(),
+                    _ => {
+                        stream.pos -= 1;
+                        return Err(stream.unexpected("a quoted variable name"));
+                    }
+                };
+                if !stream.eat(&TokenKind::RBracket) {
+                    return Err(stream.unexpected("`]`"));
+                }
+                return Ok(host.env(&name).map_or(Value::Nil, Value::Str));
+            }
+            expect_punct(stream, ".")?;
+            match method_name(stream)?.as_str() {
+                "key?" | "has_key?" | "include?" => {
+                    let name = string_argument(stream, host)?;
+                    Ok(Value::Bool(host.env(&name).is_some()))
+                }
+                "fetch" => {
+                    expect_punct(stream, "(")?;
+                    let name = match or_expr(stream, host)? {
+                        Value::Str(name) => name,
+                        _ => return Err(stream.unexpected("a string argument")),
+                    };
+                    let default = if stream.eat(&TokenKind::Comma) {
+                        or_expr(stream, host)?
+                    } else {
+                        Value::Nil
+                    };
+                    expect_punct(stream, ")")?;
+                    Ok(host.env(&name).map_or(default, Value::Str))
+                }
+                other => Err(unsupported(stream, &format!("ENV.{other}"))),
+            }
+        }
+        "File" => {
+            expect_punct(stream, ".")?;
+            match method_name(stream)?.as_str() {
+                "exist?" | "exists?" => {
+                    let path = string_argument(stream, host)?;
+                    Ok(Value::Bool(Path::new(&path).exists()))
+                }
+                "file?" => {
+                    let path = string_argument(stream, host)?;
+                    Ok(Value::Bool(Path::new(&path).is_file()))
+                }
+                "directory?" => {
+                    let path = string_argument(stream, host)?;
+                    Ok(Value::Bool(Path::new(&path).is_dir()))
+                }
+                "expand_path" => {
+                    let path = string_argument(stream, host)?;
+                    Ok(Value::Str(expand_tilde(&path, host)))
+                }
+                other => Err(unsupported(stream, &format!("File.{other}"))),
+            }
+        }
+        other => Err(unsupported(stream, other)),
+    }
+}
+
+fn expand_tilde(path: &str, host: &Host) -> String {
+    let home = host.env("HOME").unwrap_or_default();
+    if path == "~" {
+        home
+    } else if let Some(rest) = path.strip_prefix("~/") {
+        format!("{home}/{rest}")
+    } else {
+        path.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tokenize;
+    use super::*;
+
+    /// Not expected to be set in any environment the tests run in.
+    const UNSET: &str = "INSTALL_TOOLS_TUI_TEST_UNSET";
+
+    const MAC_ARM: Host = Host {
+        os: "macos",
+        arch: "aarch64",
+    };
+    const LINUX_INTEL: Host = Host {
+        os: "linux",
+        arch: "x86_64",
+    };
+
+    fn eval(condition: &str, host: &Host) -> Result<bool, String> {
+        let tokens = tokenize(condition).map_err(|err| err.message)?;
+        evaluate(&tokens, host).map_err(|err| format!("{}: {}", err.column, err.message))
+    }
+
+    fn holds(condition: &str) -> bool {
+        eval(condition, &MAC_ARM).unwrap_or_else(|err| panic!("{condition}: {err}"))
+    }
+
+    #[test]
+    fn checks_the_os_and_cpu() {
+        assert_eq!(eval("OS.mac?", &MAC_ARM), Ok(true));
+        assert_eq!(eval("OS.mac?", &LINUX_INTEL), Ok(false));
+        assert_eq!(eval("OS.linux?", &LINUX_INTEL), Ok(true));
+        assert_eq!(eval("Hardware::CPU.arm?", &MAC_ARM), Ok(true));
+        assert_eq!(eval("Hardware::CPU.intel?", &MAC_ARM), Ok(false));
+        assert_eq!(eval("Hardware::CPU.intel?", &LINUX_INTEL), Ok(true));
+    }
+
+    #[test]
+    fn combines_with_and_or_not() {
+        assert!(holds("OS.mac? && Hardware::CPU.arm?"));
+        assert!(!holds("OS.mac? && Hardware::CPU.intel?"));
+        assert!(holds("OS.linux? || Hardware::CPU.arm?"));
+        assert!(!holds("OS.linux? || Hardware::CPU.intel?"));
+        assert!(holds("!OS.linux?"));
+        assert!(holds("!!OS.mac?"));
+        assert!(holds("not OS.linux? and OS.mac?"));
+        assert!(holds("OS.linux? or OS.mac?"));
+        // `&&` binds tighter than `||`.
+        assert!(holds("true || false && false"));
+        assert!(!holds("(true || false) && false"));
+    }
+
+    #[test]
+    fn follows_ruby_truthiness() {
+        assert!(!holds("nil"));
+        assert!(!holds("false"));
+        assert!(holds("\"\""));
+        assert!(holds("!nil"));
+        assert!(holds("nil || \"fallback\""));
+    }
+
+    #[test]
+    fn reads_the_environment() {
+        assert!(holds(r#"ENV["PATH"]"#));
+        assert!(!holds(&format!(r#"ENV["{UNSET}"]"#)));
+        assert!(holds(&format!(r#"ENV["{UNSET}"].nil?"#)));
+        assert!(!holds(r#"ENV["PATH"].nil?"#));
+        assert!(holds(&format!(r#"!ENV["{UNSET}"]"#)));
+        assert!(holds(r#"ENV.key?("PATH")"#));
+        assert!(!holds(&format!(r#"ENV.include?("{UNSET}")"#)));
+        assert!(holds(&format!(r#"ENV.fetch("{UNSET}", "work") == "work""#)));
+        assert!(holds(&format!(r#"ENV.fetch("{UNSET}").nil?"#)));
+        assert!(holds(&format!(r#"ENV["{UNSET}"] != "1""#)));
+    }
+
+    #[test]
+    fn compares_strings_and_checks_emptiness() {
+        assert!(holds(r#""a" == "a""#));
+        assert!(holds(r#""a" != "b""#));
+        assert!(holds(r#""".empty?"#));
+        assert!(!holds(r#""x".empty?"#));
+        assert!(holds("nil == nil"));
+    }
+
+    #[test]
+    fn checks_files() {
+        assert!(holds(r#"File.exist?("/")"#));
+        assert!(holds(r#"File.directory?("/")"#));
+        assert!(!holds(r#"File.file?("/")"#));
+        assert!(!holds(r#"File.exist?("/no/such/path/anywhere")"#));
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_evaluate() {
+        let error = |condition: &str| match eval(condition, &MAC_ARM) {
+            Ok(value) => panic!("{condition} evaluated to {value}"),
+            Err(err) => err,
+        };
+        assert_eq!(error("MacOS.version"), "1: unsupported condition `MacOS`");
+        assert_eq!(
+            error("OS.windows?"),
+            "4: unsupported condition `OS.windows?`"
+        );
+        assert_eq!(error(r#""x".length"#), "5: unsupported condition `length`");
+        assert_eq!(
+            error("OS.mac? OS"),
+            "9: expected end of condition, found `OS`"
+        );
+        assert_eq!(error(r#"ENV["CI""#), "9: expected `]`");
+        assert_eq!(error("(OS.mac?"), "9: expected `)`");
+        assert_eq!(error("OS.mac? &&"), "11: expected a condition");
+        assert_eq!(error(""), "1: expected a condition");
+    }
+}
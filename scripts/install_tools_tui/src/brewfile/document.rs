@@ -0,0 +1,321 @@
+//! Line-preserving view of a Brewfile used to write selections back out.
+//! Untouched lines are reproduced byte for byte, so comments, ordering and
+//! blank-line grouping survive a round trip.
+
+use std::collections::HashSet;
+
+use super::{Entry, EntryKind};
+
+#[derive(Clone, Debug)]
+struct DocLine {
+    text: String,
+    /// Line number in the original file; `None` for inserted lines.
+    origin: Option<usize>,
+    /// Set for top-level entries, which are valid insertion points.
+    entry: Option<EntryKind>,
+    /// Original line of the description comment the parser attached to
+    /// this entry.
+    description: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Document {
+    lines: Vec<DocLine>,
+    trailing_newline: bool,
+}
+
+impl Document {
+    /// Builds a document from the file contents and the entries `parse`
+    /// found in them.
+    pub fn new(contents: &str, entries: &[Entry]) -> Self {
+        // Split on `\n` alone so a `\r` before it stays part of the line.
+        let texts: Vec<&str> = if contents.is_empty() {
+            Vec::new()
+        } else {
+            let body = contents.strip_suffix('\n').unwrap_or(contents);
+            body.split('\n').collect()
+        };
+        let lines = texts
+            .into_iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let line = index + 1;
+                let entry = entries.iter().find(|entry| entry.line == line);
+                DocLine {
+                    text: text.to_string(),
+                    origin: Some(line),
+                    entry: entry.filter(|entry| !entry.nested).map(|entry| entry.kind),
+                    description: entry.and_then(|entry| entry.description_line),
+                }
+            })
+            .collect();
+        Document {
+            lines,
+            trailing_newline: contents.is_empty() || contents.ends_with('\n'),
+        }
+    }
+
+    /// Removes the entries on the given original lines together with the
+    /// comment line directly above each that the parser took as its
+    /// description. Section headings, titles and the rest of a longer
+    /// comment block stay.
+    pub fn remove_lines(&mut self, remove: &HashSet<usize>) {
+        let removed = |line: &DocLine| line.origin.is_some_and(|origin| remove.contains(&origin));
+        let descriptions: HashSet<usize> = self
+            .lines
+            .iter()
+            .filter(|line| removed(line))
+            .filter_map(|line| line.description)
+            .collect();
+        let drop: Vec<bool> = self
+            .lines
+            .iter()
+            .map(|line| {
+                removed(line)
+                    || (line
+                        .origin
+                        .is_some_and(|origin| descriptions.contains(&origin))
+                        && is_comment(&line.text))
+            })
+            .collect();
+
+        let mut kept: Vec<DocLine> = Vec::with_capacity(self.lines.len());
+        let mut at_seam = false;
+        for (line, dropped) in self.lines.drain(..).zip(drop) {
+            if dropped {
+                at_seam = true;
+                continue;
+            }
+            // Removing a whole group would otherwise leave two blank lines
+            // (or a leading blank line) behind.
+            let redundant_blank = at_seam
+                && is_blank(&line.text)
+                && kept.last().is_none_or(|prev| is_blank(&prev.text));
+            at_seam = false;
+            if !redundant_blank {
+                kept.push(line);
+            }
+        }
+        while kept.last().is_some_and(|line| is_blank(&line.text)) && at_seam {
+            kept.pop();
+        }
+        self.lines = kept;
+    }
+
+    /// Inserts an entry after the last top-level entry of the same kind, or
+    /// in a new group at the end of the file, preceded by its description.
+    /// Inserted lines follow the file's `\r\n` line endings if it has them.
+    pub fn insert_entry(&mut self, kind: EntryKind, text: &str, description: Option<&str>) {
+        let cr = if self
+            .lines
+            .first()
+            .is_some_and(|line| line.text.ends_with('\r'))
+        {
+            "\r"
+        } else {
+            ""
+        };
+        let mut new_lines = Vec::new();
+        if let Some(description) = description {
+            new_lines.push(DocLine {
+                text: format!("# {description}{cr}"),
+                origin: None,
+                entry: None,
+                description: None,
+            });
+        }
+        new_lines.push(DocLine {
+            text: format!("{text}{cr}"),
+            origin: None,
+            entry: Some(kind),
+            description: None,
+        });
+
+        let position = self
+            .lines
+            .iter()
+            .rposition(|line| line.entry == Some(kind))
+            .map(|index| index + 1);
+        match position {
+            Some(index) => {
+                self.lines.splice(index..index, new_lines);
+            }
+            None => {
+                if self.lines.last().is_some_and(|line| !is_blank(&line.text)) {
+                    self.lines.push(DocLine {
+                        text: cr.to_string(),
+                        origin: None,
+                        entry: None,
+                        description: None,
+                    });
+                }
+                self.lines.extend(new_lines);
+            }
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = self
+            .lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if self.trailing_newline && !self.lines.is_empty() {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+fn is_blank(text: &str) -> bool {
+    text.trim().is_empty()
+}
+
+fn is_comment(text: &str) -> bool {
+    text.trim_start().starts_with('#')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Host, parse};
+    use super::*;
+
+    const HOST: Host = Host {
+        os: "macos",
+        arch: "aarch64",
+    };
+
+    fn document(contents: &str) -> Document {
+        Document::new(contents, &parse(contents, &HOST).entries)
+    }
+
+    fn without(contents: &str, lines: &[usize]) -> String {
+        let mut document = document(contents);
+        document.remove_lines(&lines.iter().copied().collect());
+        document.render()
+    }
+
+    #[test]
+    fn renders_untouched_files_byte_for_byte() {
+        let files = [
+            "",
+            "\n",
+            "\n\n",
+            "brew \"jq\"",
+            "brew \"jq\"\n",
+            "brew \"jq\"\n\n\n",
+            "tap \"a/b\"\r\nbrew \"a/b/c\"\r\n",
+            "# ---- Dev ----\n# JSON\nbrew \"jq\", args: [\"HEAD\"]  # pinned\n\n\
+             if OS.mac?\n  cask \"firefox\"\nend\n   \n\tbrew \"wget\" unless OS.linux?",
+            "not a brewfile ; at all\n\u{feff}odd bytes é\n",
+        ];
+        for contents in files {
+            assert_eq!(document(contents).render(), contents, "{contents:?}");
+        }
+    }
+
+    #[test]
+    fn removes_an_entry_with_its_description() {
+        let contents = "brew \"jq\"\n# Downloads files\nbrew \"wget\"\nbrew \"fd\"\n";
+        assert_eq!(without(contents, &[3]), "brew \"jq\"\nbrew \"fd\"\n");
+    }
+
+    #[test]
+    fn keeps_section_headings_titles_and_commented_out_entries() {
+        let contents = concat!(
+            "# ==== Dev ====\n",
+            "brew \"jq\"\n",
+            "\n",
+            "# Browsers\n",
+            "\n",
+            "cask \"firefox\"\n",
+            "\n",
+            "# brew \"old\"\n",
+            "brew \"wget\"\n",
+        );
+        assert_eq!(
+            without(contents, &[2, 6, 9]),
+            "# ==== Dev ====\n\n# Browsers\n\n# brew \"old\"\n"
+        );
+    }
+
+    #[test]
+    fn keeps_all_but_the_last_line_of_a_comment_block() {
+        let contents = "# Search tools\n# Fast grep\nbrew \"ripgrep\"\nbrew \"fd\"\n";
+        assert_eq!(without(contents, &[3]), "# Search tools\nbrew \"fd\"\n");
+    }
+
+    #[test]
+    fn collapses_blank_lines_left_by_a_removed_group() {
+        let contents = "brew \"jq\"\n\n# Browsers\ncask \"firefox\"\n\nbrew \"fd\"\n";
+        assert_eq!(without(contents, &[4]), "brew \"jq\"\n\nbrew \"fd\"\n");
+        assert_eq!(
+            without(contents, &[6]),
+            "brew \"jq\"\n\n# Browsers\ncask \"firefox\"\n"
+        );
+        assert_eq!(
+            without("brew \"jq\"\n\nbrew \"fd\"\n", &[1]),
+            "brew \"fd\"\n"
+        );
+    }
+
+    #[test]
+    fn inserts_after_the_last_entry_of_the_same_kind() {
+        let mut document = document("# Tools\nbrew \"jq\"\n\n# Apps\ncask \"firefox\"\n");
+        document.insert_entry(EntryKind::Brew, "brew \"fd\"", Some("Finds files"));
+        document.insert_entry(EntryKind::Cask, "cask \"iterm2\"", None);
+        assert_eq!(
+            document.render(),
+            "# Tools\nbrew \"jq\"\n# Finds files\nbrew \"fd\"\n\n# Apps\ncask \"firefox\"\ncask \"iterm2\"\n"
+        );
+    }
+
+    #[test]
+    fn does_not_insert_into_conditional_blocks() {
+        let mut document = document("if OS.mac?\n  brew \"jq\"\nend\n");
+        document.insert_entry(EntryKind::Brew, "brew \"fd\"", None);
+        assert_eq!(
+            document.render(),
+            "if OS.mac?\n  brew \"jq\"\nend\n\nbrew \"fd\"\n"
+        );
+    }
+
+    #[test]
+    fn starts_a_new_group_for_a_new_kind() {
+        let mut unterminated = document("brew \"jq\"");
+        unterminated.insert_entry(EntryKind::Vscode, "vscode \"a.b\"", Some("Extension"));
+        assert_eq!(
+            unterminated.render(),
+            "brew \"jq\"\n\n# Extension\nvscode \"a.b\""
+        );
+
+        let mut crlf = document("brew \"jq\"\r\n");
+        crlf.insert_entry(EntryKind::Cask, "cask \"x\"", None);
+        assert_eq!(crlf.render(), "brew \"jq\"\r\n\r\ncask \"x\"\r\n");
+
+        let mut empty = document("");
+        empty.insert_entry(EntryKind::Brew, "brew \"jq\"", None);
+        assert_eq!(empty.render(), "brew \"jq\"\n");
+    }
+
+    #[test]
+    fn removes_then_inserts_around_comment_groups() {
+        let contents = concat!(
+            "# ---- CLI ----\n",
+            "# JSON processor\n",
+            "brew \"jq\"\n",
+            "brew \"wget\"\n",
+            "\n",
+            "# ---- Apps ----\n",
+            "cask \"firefox\"\n",
+        );
+        let mut document = document(contents);
+        document.remove_lines(&[3].into_iter().collect());
+        document.insert_entry(EntryKind::Brew, "brew \"fd\"", None);
+        assert_eq!(
+            document.render(),
+            "# ---- CLI ----\nbrew \"wget\"\nbrew \"fd\"\n\n# ---- Apps ----\ncask \"firefox\"\n"
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Command-line options.
+
+use std::path::PathBuf;
+
+use crate::ToolKind;
+use crate::brewfile::EntryKind;
+
+pub const USAGE: &str = "\
+Usage: install_tools_tui [command] [options]
+
+Commands:
+  tui       Choose and install entries interactively (the default)
+  install   Install a selection without prompting, printing progress lines
+  list      List the Brewfile entries
+  status    Show which Brewfile entries are installed
+  diff      Show entries that are missing and installed software not listed
+  export    Print what is installed as a Brewfile
+  doctor    Check the tools the installer relies on and the Brewfile
+
+Without a command, entries are installed without prompting when a selection
+flag is given or stdout is not a terminal.
+
+Options:
+  --brewfile <path-or-url>  Read this Brewfile instead of the configured one
+  --format <text|json>      Output format for list, status, diff, export,
+                            doctor and install (default: text)
+  --headless                Same as the install command
+  --all                     Select every Brewfile entry
+  --only <names>            Select only these entries (comma-separated)
+  --kind <kinds>            Select only these kinds: tap, brew, cask, mas, vscode, go
+  --exclude <names>         Leave these entries out (comma-separated)
+  --report-json <path>      Write a JSON report of the run to <path>
+  --report-junit <path>     Write a JUnit XML report of the run to <path>
+  -h, --help                Show this help
+
+Exits with status 2 when any item failed or was cancelled, or when doctor
+finds a problem.";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Command {
+    /// No command was given; decided by the selection flags and terminal.
+    #[default]
+    Default,
+    Tui,
+    Install,
+    List,
+    Status,
+    Diff,
+    Export,
+    Doctor,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tui" => Some(Command::Tui),
+            "install" => Some(Command::Install),
+            "list" => Some(Command::List),
+            "status" => Some(Command::Status),
+            "diff" => Some(Command::Diff),
+            "export" => Some(Command::Export),
+            "doctor" => Some(Command::Doctor),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub command: Command,
+    pub help: bool,
+    /// Brewfile path or URL given with `--brewfile`, taking precedence over
+    /// the environment.
+    pub brewfile: Option<String>,
+    pub format: Format,
+    pub headless: bool,
+    pub all: bool,
+    pub only: Vec<String>,
+    pub kinds: Vec<ToolKind>,
+    pub exclude: Vec<String>,
+    pub report_json: Option<PathBuf>,
+    pub report_junit: Option<PathBuf>,
+}
+
+/// Parses the arguments after the program name. Values may follow their
+/// flag as the next argument or after `=`.
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value)),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| -> Result<String, String> {
+            match inline {
+                Some(value) => Ok(value.to_string()),
+                None => args
+                    .next()
+                    .ok_or_else(|| format!("{name} requires a value")),
+            }
+        };
+        match flag.as_str() {
+            "-h" | "--help" => options.help = true,
+            "--headless" => options.headless = true,
+            "--all" => options.all = true,
+            "--only" => options.only.extend(list(&value(&flag)?)),
+            "--exclude" => options.exclude.extend(list(&value(&flag)?)),
+            "--kind" => {
+                for word in list(&value(&flag)?) {
+                    let kind = EntryKind::from_keyword(&word)
+                        .ok_or_else(|| format!("Unknown kind `{word}` for --kind"))?;
+                    options.kinds.push(ToolKind::from_entry_kind(kind));
+                }
+            }
+            "--brewfile" => options.brewfile = Some(value(&flag)?),
+            "--format" => {
+                options.format = match value(&flag)?.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    other => return Err(format!("Unknown format `{other}` for --format")),
+                }
+            }
+            "--report-json" => options.report_json = Some(PathBuf::from(value(&flag)?)),
+            "--report-junit" => options.report_junit = Some(PathBuf::from(value(&flag)?)),
+            _ if options.command == Command::Default && !arg.starts_with('-') => {
+                options.command = Command::from_name(&arg)
+                    .ok_or_else(|| format!("Unknown command `{arg}`\n\n{USAGE}"))?;
+            }
+            _ => return Err(format!("Unknown argument `{arg}`\n\n{USAGE}")),
+        }
+    }
+    Ok(options)
+}
+
+impl Options {
+    /// Whether any flag chose entries to install.
+    pub fn has_selection(&self) -> bool {
+        self.all || !self.only.is_empty() || !self.kinds.is_empty() || !self.exclude.is_empty()
+    }
+
+    /// The command to run. Without one, entries are installed headlessly
+    /// when asked to, when a selection flag is given, or when stdout is not
+    /// a terminal; otherwise the interface starts.
+    pub fn resolved_command(&self, stdout_is_terminal: bool) -> Command {
+        match self.command {
+            Command::Default if self.headless || self.has_selection() || !stdout_is_terminal => {
+                Command::Install
+            }
+            Command::Default => Command::Tui,
+            command => command,
+        }
+    }
+}
+
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Options, String> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn takes_values_after_equals_or_as_the_next_argument() {
+        let joined = parse_args(&["--only=jq,rg", "--brewfile=a=b.rb", "--format=json"]).unwrap();
+        let split = parse_args(&[
+            "--only",
+            "jq,rg",
+            "--brewfile",
+            "a=b.rb",
+            "--format",
+            "json",
+        ])
+        .unwrap();
+        for options in [joined, split] {
+            assert_eq!(options.only, ["jq", "rg"]);
+            assert_eq!(options.brewfile.as_deref(), Some("a=b.rb"));
+            assert_eq!(options.format, Format::Json);
+        }
+    }
+
+    #[test]
+    fn collects_lists_and_kinds() {
+        let options = parse_args(&[
+            "install",
+            "--kind",
+            "brew, cask",
+            "--exclude",
+            "a,,b",
+            "--exclude=c",
+        ])
+        .unwrap();
+        assert_eq!(options.command, Command::Install);
+        assert_eq!(options.kinds, [ToolKind::BrewFormula, ToolKind::BrewCask]);
+        assert_eq!(options.exclude, ["a", "b", "c"]);
+        assert!(options.has_selection());
+    }
+
+    #[test]
+    fn reports_missing_values() {
+        assert_eq!(
+            parse_args(&["--only"]).unwrap_err(),
+            "--only requires a value"
+        );
+        assert_eq!(
+            parse_args(&["--all", "--report-json"]).unwrap_err(),
+            "--report-json requires a value"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_kinds_and_formats() {
+        assert_eq!(
+            parse_args(&["--kind", "brew,pip"]).unwrap_err(),
+            "Unknown kind `pip` for --kind"
+        );
+        assert_eq!(
+            parse_args(&["--format=yaml"]).unwrap_err(),
+            "Unknown format `yaml` for --format"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_commands_and_a_second_command() {
+        assert!(
+            parse_args(&["upgrade"])
+                .unwrap_err()
+                .starts_with("Unknown command `upgrade`")
+        );
+        assert!(
+            parse_args(&["list", "status"])
+                .unwrap_err()
+                .starts_with("Unknown argument `status`")
+        );
+        assert!(
+            parse_args(&["--bogus"])
+                .unwrap_err()
+                .starts_with("Unknown argument `--bogus`")
+        );
+    }
+
+    #[test]
+    fn default_command_depends_on_flags_and_terminal() {
+        let default = parse_args(&[]).unwrap();
+        assert_eq!(default.resolved_command(true), Command::Tui);
+        assert_eq!(default.resolved_command(false), Command::Install);
+        for flag in ["--headless", "--all", "--only=jq"] {
+            let options = parse_args(&[flag]).unwrap();
+            assert_eq!(options.resolved_command(true), Command::Install, "{flag}");
+        }
+        let tui = parse_args(&["tui", "--all"]).unwrap();
+        assert_eq!(tui.resolved_command(false), Command::Tui);
+        let list = parse_args(&["list"]).unwrap();
+        assert_eq!(list.resolved_command(false), Command::List);
+    }
+}
@@ -0,0 +1,304 @@
+//! Subcommands that print information instead of opening the interface.
+
+use std::io::{self, Write};
+
+use serde_json::{Value, json};
+
+use crate::brewfile::{EntryKind, Severity};
+use crate::cli::{Command, Format, Options};
+use crate::drift::{self, DriftState};
+use crate::inventory::Inventory;
+use crate::{
+    LoadedBrewfile, ToolState, brew_available, code_command, go_available, history,
+    load_tools_from_brewfile, mas_available,
+};
+
+/// Why a command that prints to stdout stopped early.
+#[derive(Debug)]
+pub enum CommandError {
+    /// The reader closed stdout, as `head` does after enough lines. `main`
+    /// exits quietly on it.
+    OutputClosed,
+    Failed(String),
+}
+
+impl From<String> for CommandError {
+    fn from(err: String) -> Self {
+        CommandError::Failed(err)
+    }
+}
+
+impl From<CommandError> for io::Error {
+    fn from(err: CommandError) -> Self {
+        match err {
+            CommandError::OutputClosed => io::ErrorKind::BrokenPipe.into(),
+            CommandError::Failed(err) => io::Error::other(err),
+        }
+    }
+}
+
+/// Runs `list`, `status`, `diff`, `export` or `doctor` and returns whether
+/// it found nothing wrong.
+pub fn run(options: &Options) -> Result<bool, CommandError> {
+    match options.command {
+        Command::List => list(options),
+        Command::Status => status(options, false),
+        Command::Diff => status(options, true),
+        Command::Export => export(options.format),
+        Command::Doctor => doctor(options),
+        command => Err(format!("{command:?} does not print a report").into()),
+    }
+}
+
+fn load(options: &Options) -> Result<LoadedBrewfile, String> {
+    let loaded = load_tools_from_brewfile(options.brewfile.as_deref())?;
+    if let Some(note) = &loaded.note {
+        eprintln!("{note}");
+    }
+    Ok(loaded)
+}
+
+fn list(options: &Options) -> Result<bool, CommandError> {
+    let loaded = load(options)?;
+    for diagnostic in &loaded.diagnostics {
+        eprintln!("Brewfile:{diagnostic}");
+    }
+    match options.format {
+        Format::Text => print_lines(loaded.items.iter().map(|item| {
+            let mut line = format!("{:<7} {}", keyword(item), item.tool.name);
+            if !item.tool.description.is_empty() {
+                line.push_str(&format!("  # {}", item.tool.description));
+            }
+            if let Some(reason) = &item.excluded {
+                line.push_str(&format!("  (skipped: {reason})"));
+            }
+            line
+        }))?,
+        Format::Json => print_json(&Value::Array(loaded.items.iter().map(entry_json).collect()))?,
+    }
+    Ok(true)
+}
+
+/// `status` classifies the Brewfile entries as installed or missing; `diff`
+/// shows only the differences, including installed software the Brewfile
+/// does not list.
+fn status(options: &Options, diff: bool) -> Result<bool, CommandError> {
+    let loaded = load(options)?;
+    let inventory = Inventory::collect();
+    for warning in &inventory.warnings {
+        eprintln!("{warning}");
+    }
+    let items: Vec<_> = drift::compute(&loaded.items, &inventory)
+        .into_iter()
+        .filter(|item| {
+            if diff {
+                item.state != DriftState::Installed
+            } else {
+                item.state != DriftState::Extra
+            }
+        })
+        .collect();
+    match options.format {
+        Format::Text => print_lines(items.iter().map(|item| {
+            if diff {
+                let sign = if item.state == DriftState::Missing {
+                    '-'
+                } else {
+                    '+'
+                };
+                format!("{sign} {} {}", item.kind.keyword(), item.name)
+            } else {
+                format!(
+                    "{:<9} {:<7} {}",
+                    item.state.label(),
+                    item.kind.keyword(),
+                    item.name
+                )
+            }
+        }))?,
+        Format::Json => print_json(&Value::Array(
+            items
+                .iter()
+                .map(|item| {
+                    json!({
+                        "state": item.state.label(),
+                        "kind": item.kind.keyword(),
+                        "name": item.name,
+                        "app_id": item.app_id,
+                        "entry": item.entry_line,
+                    })
+                })
+                .collect(),
+        ))?,
+    }
+    Ok(true)
+}
+
+fn export(format: Format) -> Result<bool, CommandError> {
+    let inventory = Inventory::collect();
+    for warning in &inventory.warnings {
+        eprintln!("{warning}");
+    }
+    match format {
+        Format::Text => print_text(&inventory.to_brewfile())?,
+        Format::Json => {
+            let names = |kind: EntryKind, names: &[String]| {
+                names
+                    .iter()
+                    .map(move |name| json!({ "kind": kind.keyword(), "name": name }))
+                    .collect::<Vec<_>>()
+            };
+            let mut entries = names(EntryKind::Tap, &inventory.taps);
+            entries.extend(names(EntryKind::Brew, &inventory.formulae));
+            entries.extend(names(EntryKind::Cask, &inventory.casks));
+            entries.extend(inventory.mas.iter().map(|(id, name)| {
+                json!({ "kind": EntryKind::Mas.keyword(), "name": name, "app_id": id })
+            }));
+            entries.extend(names(EntryKind::Vscode, &inventory.vscode));
+            print_json(&Value::Array(entries))?;
+        }
+    }
+    Ok(true)
+}
+
+/// Checks for the command-line tools each entry kind needs, that the
+/// Brewfile loads and parses cleanly, and that run history can be saved.
+/// Only a missing Homebrew or an unreadable Brewfile count as failures.
+fn doctor(options: &Options) -> Result<bool, CommandError> {
+    let mut checks: Vec<(&str, bool, bool, String)> = Vec::new();
+    let tool = |available: bool, found: &str, missing: &str| {
+        if available { found } else { missing }.to_string()
+    };
+    checks.push((
+        "homebrew",
+        brew_available(),
+        true,
+        tool(
+            brew_available(),
+            "brew found",
+            "brew not found; install Homebrew first",
+        ),
+    ));
+    checks.push((
+        "mas",
+        mas_available(),
+        false,
+        tool(
+            mas_available(),
+            "mas found",
+            "mas not found; App Store entries cannot be installed",
+        ),
+    ));
+    let code = code_command();
+    checks.push((
+        "vscode",
+        code.is_some(),
+        false,
+        code.unwrap_or_else(|| "code not found; VS Code extensions cannot be installed".into()),
+    ));
+    checks.push((
+        "go",
+        go_available(),
+        false,
+        tool(
+            go_available(),
+            "go found",
+            "go not found; Go entries cannot be installed",
+        ),
+    ));
+    match load_tools_from_brewfile(options.brewfile.as_deref()) {
+        Ok(loaded) => {
+            let dropped = loaded
+                .diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == Severity::Ignored)
+                .count();
+            checks.push((
+                "brewfile",
+                loaded.diagnostics.is_empty(),
+                false,
+                format!(
+                    "{} entries, {} warnings, {dropped} ignored lines",
+                    loaded.items.len(),
+                    loaded.diagnostics.len() - dropped
+                ),
+            ));
+        }
+        Err(err) => checks.push(("brewfile", false, true, err)),
+    }
+    checks.push(match history::state_dir() {
+        Some(dir) => ("history", true, false, dir.display().to_string()),
+        None => (
+            "history",
+            false,
+            false,
+            "cannot locate a state directory; HOME is not set".to_string(),
+        ),
+    });
+
+    let healthy = checks.iter().all(|(_, ok, required, _)| *ok || !*required);
+    match options.format {
+        Format::Text => print_lines(checks.iter().map(|(name, ok, required, detail)| {
+            let mark = match (ok, required) {
+                (true, _) => "ok",
+                (false, true) => "FAIL",
+                (false, false) => "warn",
+            };
+            format!("[{mark:<4}] {name:<9} {detail}")
+        }))?,
+        Format::Json => print_json(&json!({
+            "healthy": healthy,
+            "checks": checks
+                .iter()
+                .map(|(name, ok, required, detail)| {
+                    json!({ "name": name, "ok": ok, "required": required, "detail": detail })
+                })
+                .collect::<Vec<_>>(),
+        }))?,
+    }
+    Ok(healthy)
+}
+
+fn keyword(item: &ToolState) -> &'static str {
+    item.tool.kind.entry_kind().keyword()
+}
+
+fn entry_json(item: &ToolState) -> Value {
+    json!({
+        "kind": keyword(item),
+        "name": item.tool.name,
+        "label": item.tool.label,
+        "description": item.tool.description,
+        "category": item.tool.category,
+        "app_id": item.tool.app_id,
+        "version": item.tool.version,
+        "skipped": item.excluded,
+    })
+}
+
+pub fn print_json(value: &Value) -> Result<(), CommandError> {
+    let text = serde_json::to_string_pretty(value)
+        .map_err(|err| format!("Failed to encode output: {err}"))?;
+    print_lines([text])
+}
+
+fn print_lines(lines: impl IntoIterator<Item = String>) -> Result<(), CommandError> {
+    let mut out = io::stdout().lock();
+    for line in lines {
+        writeln!(out, "{line}").map_err(output_error)?;
+    }
+    out.flush().map_err(output_error)
+}
+
+fn print_text(text: &str) -> Result<(), CommandError> {
+    let mut out = io::stdout().lock();
+    out.write_all(text.as_bytes()).map_err(output_error)?;
+    out.flush().map_err(output_error)
+}
+
+fn output_error(err: io::Error) -> CommandError {
+    if err.kind() == io::ErrorKind::BrokenPipe {
+        return CommandError::OutputClosed;
+    }
+    CommandError::Failed(format!("Failed to write output: {err}"))
+}
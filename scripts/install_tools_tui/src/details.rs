@@ -0,0 +1,309 @@
+//! Metadata for the selection screen's detail pane: `brew info --json=v2`
+//! for formulae and casks, the App Store lookup API for `mas` entries.
+
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+use serde_json::Value;
+
+use crate::{Tool, ToolKind, run_command};
+
+const APP_STORE_LOOKUP_URL: &str = "https://itunes.apple.com/lookup";
+
+/// Where the details of an entry stand in the pane's cache.
+#[derive(Clone, Debug)]
+pub enum Lookup {
+    Loading,
+    Ready(Details),
+    Failed(String),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Details {
+    /// Labelled values in display order, such as `("License", "MIT")`.
+    pub fields: Vec<(&'static str, String)>,
+    pub caveats: Option<String>,
+}
+
+/// Fetches the details of `tool` on a background thread and sends them,
+/// tagged with `key`, on `sender`.
+pub fn spawn<K: Send + 'static>(key: K, tool: Tool, sender: Sender<(K, Result<Details, String>)>) {
+    thread::spawn(move || {
+        let _ = sender.send((key, fetch(&tool)));
+    });
+}
+
+pub fn fetch(tool: &Tool) -> Result<Details, String> {
+    match tool.kind {
+        ToolKind::BrewFormula => brew_info("--formula", &tool.name).map(|info| formula(&info)),
+        ToolKind::BrewCask => brew_info("--cask", &tool.name).map(|info| cask(&info)),
+        ToolKind::Mas => app_store(tool.app_id.as_deref().unwrap_or_default()),
+        ToolKind::Tap => {
+            let url = tool.options.tap_url.clone().unwrap_or_else(|| {
+                let (owner, repo) = tool.name.split_once('/').unwrap_or((&tool.name, ""));
+                format!("https://github.com/{owner}/homebrew-{repo}")
+            });
+            Ok(Details {
+                fields: vec![("Repository", url)],
+                caveats: None,
+            })
+        }
+        ToolKind::VsCode => Ok(Details {
+            fields: vec![(
+                "Homepage",
+                format!(
+                    "https://marketplace.visualstudio.com/items?itemName={}",
+                    tool.name
+                ),
+            )],
+            caveats: None,
+        }),
+        ToolKind::Go => Ok(Details {
+            fields: vec![
+                ("Module", tool.name.clone()),
+                (
+                    "Version",
+                    tool.version.clone().unwrap_or_else(|| "latest".to_string()),
+                ),
+            ],
+            caveats: None,
+        }),
+    }
+}
+
+/// The first formula or cask of `brew info --json=v2`.
+fn brew_info(flag: &str, name: &str) -> Result<Value, String> {
+    let output = run_command(&["brew", "info", "--json=v2", flag, name])
+        .map_err(|err| format!("Failed to run brew info: {err}"))?;
+    if !output.status.success() {
+        return Err(format!("brew info failed: {}", output.failure_message()));
+    }
+    let mut info: Value = serde_json::from_str(&output.stdout)
+        .map_err(|err| format!("Could not read brew info output: {err}"))?;
+    let list = if flag == "--cask" {
+        "casks"
+    } else {
+        "formulae"
+    };
+    info.get_mut(list)
+        .and_then(Value::as_array_mut)
+        .and_then(|items| items.drain(..).next())
+        .ok_or_else(|| format!("brew info returned nothing for {name}"))
+}
+
+fn formula(info: &Value) -> Details {
+    let mut details = Details::default();
+    push(&mut details, "Homepage", text(info, "homepage"));
+    push(
+        &mut details,
+        "Version",
+        info.pointer("/versions/stable")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+    );
+    let installed: Vec<&str> = info
+        .get("installed")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|keg| keg.get("version").and_then(Value::as_str))
+        .collect();
+    push(
+        &mut details,
+        "Installed",
+        Some(if installed.is_empty() {
+            "no".to_string()
+        } else {
+            installed.join(", ")
+        }),
+    );
+    push(&mut details, "License", text(info, "license"));
+    push(&mut details, "Depends on", names(info.get("dependencies")));
+    push(&mut details, "Bottle", Some(bottle(info)));
+    if !installed.is_empty() {
+        let cellar = run_command(&["brew", "--cellar", &text(info, "name").unwrap_or_default()])
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| output.stdout.trim().to_string());
+        push(
+            &mut details,
+            "Size on disk",
+            cellar.map(|dir| format_size(dir_size(Path::new(&dir)))),
+        );
+    }
+    details.caveats = text(info, "caveats");
+    details
+}
+
+fn cask(info: &Value) -> Details {
+    let mut details = Details::default();
+    push(&mut details, "Homepage", text(info, "homepage"));
+    push(&mut details, "Version", text(info, "version"));
+    push(
+        &mut details,
+        "Installed",
+        Some(text(info, "installed").unwrap_or_else(|| "no".to_string())),
+    );
+    push(
+        &mut details,
+        "Depends on",
+        names(info.pointer("/depends_on/formula")),
+    );
+    if info.get("auto_updates").and_then(Value::as_bool) == Some(true) {
+        details
+            .fields
+            .push(("Updates", "updates itself".to_string()));
+    }
+    let apps: Vec<&str> = info
+        .get("artifacts")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|artifact| artifact.get("app").and_then(Value::as_array))
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    let size: u64 = apps
+        .iter()
+        .map(|app| Path::new("/Applications").join(app))
+        .filter(|path| path.exists())
+        .map(|path| dir_size(&path))
+        .sum();
+    if size > 0 {
+        details.fields.push(("Size on disk", format_size(size)));
+    }
+    details.caveats = text(info, "caveats");
+    details
+}
+
+/// Looks an app up by its App Store id.
+fn app_store(id: &str) -> Result<Details, String> {
+    let client = Client::builder()
+        .timeout(Duration::from_secs(10))
+        .user_agent("install-tools-tui")
+        .build()
+        .map_err(|err| format!("Failed to build HTTP client: {err}"))?;
+    let response = client
+        .get(APP_STORE_LOOKUP_URL)
+        .query(&[("id", id), ("entity", "macSoftware")])
+        .send()
+        .map_err(|err| format!("App Store lookup failed: {err}"))?;
+    let info: Value = response
+        .text()
+        .ok()
+        .and_then(|body| serde_json::from_str(&body).ok())
+        .ok_or_else(|| "Could not read the App Store lookup".to_string())?;
+    let app = info
+        .pointer("/results/0")
+        .ok_or_else(|| format!("The App Store has no app with id {id}"))?;
+
+    let mut details = Details::default();
+    push(&mut details, "Homepage", text(app, "trackViewUrl"));
+    push(&mut details, "Version", text(app, "version"));
+    push(&mut details, "Seller", text(app, "sellerName"));
+    push(&mut details, "Price", text(app, "formattedPrice"));
+    push(
+        &mut details,
+        "Requires",
+        text(app, "minimumOsVersion").map(|os| format!("macOS {os}")),
+    );
+    push(
+        &mut details,
+        "Download size",
+        text(app, "fileSizeBytes")
+            .and_then(|bytes| bytes.parse().ok())
+            .map(format_size),
+    );
+    push(
+        &mut details,
+        "Rating",
+        app.get("averageUserRating")
+            .and_then(Value::as_f64)
+            .map(|rating| format!("{rating:.1} / 5")),
+    );
+    Ok(details)
+}
+
+fn push(details: &mut Details, label: &'static str, value: Option<String>) {
+    if let Some(value) = value.filter(|value| !value.trim().is_empty()) {
+        details.fields.push((label, value));
+    }
+}
+
+fn text(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+fn names(value: Option<&Value>) -> Option<String> {
+    let names: Vec<&str> = value
+        .and_then(Value::as_array)?
+        .iter()
+        .filter_map(Value::as_str)
+        .collect();
+    Some(if names.is_empty() {
+        "nothing".to_string()
+    } else {
+        names.join(", ")
+    })
+}
+
+/// Whether a bottle is published for this machine's architecture, so the
+/// formula installs without building from source.
+fn bottle(info: &Value) -> String {
+    let platforms: Vec<&String> = info
+        .pointer("/bottle/stable/files")
+        .and_then(Value::as_object)
+        .map(|files| files.keys().collect())
+        .unwrap_or_default();
+    if platforms.is_empty() {
+        return "none; builds from source".to_string();
+    }
+    let arm = std::env::consts::ARCH == "aarch64";
+    let native = platforms
+        .iter()
+        .any(|tag| *tag == "all" || tag.starts_with("arm64_") == arm);
+    if native {
+        format!("available ({} platforms)", platforms.len())
+    } else {
+        "not for this architecture; builds from source".to_string()
+    }
+}
+
+/// Bytes used by the files under `path`, not following symlinks.
+fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| dir_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    if size < 1024.0 {
+        return format!("{bytes} B");
+    }
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+    format!("{size:.1} {unit}")
+}
@@ -0,0 +1,355 @@
+//! Comparison between the loaded Brewfile and what is installed.
+
+use std::collections::HashSet;
+
+use crate::brewfile::{self, EntryKind, OptionValue};
+use crate::inventory::Inventory;
+use crate::{
+    BrewOptions, Origin, Tool, ToolKind, ToolState, go_bin_dir, go_binary_name, is_builtin_tap,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DriftState {
+    /// Listed in the Brewfile but not installed.
+    Missing,
+    /// Installed but not listed in the Brewfile.
+    Extra,
+    Installed,
+}
+
+impl DriftState {
+    pub fn label(self) -> &'static str {
+        match self {
+            DriftState::Missing => "missing",
+            DriftState::Extra => "extra",
+            DriftState::Installed => "installed",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DriftItem {
+    pub state: DriftState,
+    pub kind: EntryKind,
+    pub name: String,
+    pub label: String,
+    /// Index into `App::items` for entries that come from the Brewfile.
+    pub item: Option<usize>,
+    /// App Store id for `mas` items.
+    pub app_id: Option<String>,
+    /// The Brewfile line that would list an extra item.
+    pub entry_line: Option<String>,
+    pub selected: bool,
+}
+
+/// Classifies every applicable Brewfile entry as installed or missing, and
+/// every installed item the Brewfile does not mention as extra. Entries
+/// under a condition that could not be evaluated count as mentioned. Missing
+/// items come first, then extras, then installed ones.
+pub fn compute(items: &[ToolState], inventory: &Inventory) -> Vec<DriftItem> {
+    let lower = |values: &[String]| -> HashSet<String> {
+        values
+            .iter()
+            .map(|value| value.to_ascii_lowercase())
+            .collect()
+    };
+    let taps = lower(&inventory.taps);
+    let formulae = lower(&inventory.installed_formulae);
+    let casks = lower(&inventory.casks);
+    let extensions = lower(&inventory.vscode);
+    let mas_ids: HashSet<&str> = inventory.mas.iter().map(|(id, _)| id.as_str()).collect();
+    let go_dir = go_bin_dir();
+
+    let mut drift = Vec::new();
+    let mut listed: HashSet<(EntryKind, String)> = HashSet::new();
+    for (index, state) in items.iter().enumerate() {
+        let tool = &state.tool;
+        let kind = tool.kind.entry_kind();
+        let key = match tool.kind {
+            ToolKind::Tap | ToolKind::VsCode | ToolKind::Go => tool.name.to_ascii_lowercase(),
+            ToolKind::Mas => tool.app_id.clone().unwrap_or_default(),
+            ToolKind::BrewFormula | ToolKind::BrewCask => short_name(&tool.name),
+        };
+        match state.excluded.as_deref() {
+            None => {}
+            // It may apply here, so it is neither missing nor extra.
+            Some(brewfile::UNEVALUATED) => {
+                listed.insert((kind, key));
+                continue;
+            }
+            Some(_) => continue,
+        }
+        let installed = match tool.kind {
+            ToolKind::Tap => is_builtin_tap(&tool.name) || taps.contains(&key),
+            ToolKind::BrewFormula => formulae.contains(&key),
+            ToolKind::BrewCask => casks.contains(&key),
+            ToolKind::Mas => mas_ids.contains(key.as_str()),
+            ToolKind::VsCode => extensions.contains(&key),
+            ToolKind::Go => go_dir
+                .as_ref()
+                .is_some_and(|dir| dir.join(go_binary_name(&tool.name)).is_file()),
+        };
+        listed.insert((kind, key));
+        drift.push(DriftItem {
+            state: if installed {
+                DriftState::Installed
+            } else {
+                DriftState::Missing
+            },
+            kind,
+            name: tool.name.clone(),
+            label: tool.label.clone(),
+            item: Some(index),
+            app_id: tool.app_id.clone(),
+            entry_line: None,
+            selected: false,
+        });
+    }
+
+    let mut extra = |kind: EntryKind,
+                     key: String,
+                     name: &str,
+                     label: String,
+                     options: &[(String, OptionValue)]| {
+        if listed.insert((kind, key.clone())) {
+            drift.push(DriftItem {
+                state: DriftState::Extra,
+                kind,
+                name: name.to_string(),
+                label,
+                item: None,
+                app_id: (kind == EntryKind::Mas).then_some(key),
+                entry_line: Some(brewfile::format_entry(kind, name, options)),
+                selected: false,
+            });
+        }
+    };
+    for tap in &inventory.taps {
+        extra(
+            EntryKind::Tap,
+            tap.to_ascii_lowercase(),
+            tap,
+            format!("{tap} (tap)"),
+            &[],
+        );
+    }
+    for formula in &inventory.formulae {
+        extra(
+            EntryKind::Brew,
+            short_name(formula),
+            formula,
+            format!("{formula} (brew formula)"),
+            &[],
+        );
+    }
+    for cask in &inventory.casks {
+        extra(
+            EntryKind::Cask,
+            short_name(cask),
+            cask,
+            format!("{cask} (cask)"),
+            &[],
+        );
+    }
+    for (id, name) in &inventory.mas {
+        let options = [("id".to_string(), OptionValue::Number(id.clone()))];
+        extra(
+            EntryKind::Mas,
+            id.clone(),
+            name,
+            format!("{name} (App Store)"),
+            &options,
+        );
+    }
+    for extension in &inventory.vscode {
+        extra(
+            EntryKind::Vscode,
+            extension.to_ascii_lowercase(),
+            extension,
+            format!("{extension} (VS Code)"),
+            &[],
+        );
+    }
+
+    drift.sort_by_key(|item| item.state);
+    drift
+}
+
+/// Installed formulae, casks and App Store apps that the Brewfile does not
+/// list, as `brew bundle cleanup` would remove them. Formulae come from
+/// `brew leaves`, so dependencies of listed formulae are never offered.
+pub fn cleanup_candidates(items: &[ToolState], inventory: &Inventory) -> Vec<ToolState> {
+    compute(items, inventory)
+        .into_iter()
+        .filter(|item| item.state == DriftState::Extra)
+        .filter_map(|item| {
+            let kind = match item.kind {
+                EntryKind::Brew => ToolKind::BrewFormula,
+                EntryKind::Cask => ToolKind::BrewCask,
+                EntryKind::Mas => ToolKind::Mas,
+                _ => return None,
+            };
+            Some(ToolState {
+                tool: Tool {
+                    kind,
+                    name: item.name,
+                    label: item.label,
+                    description: "Installed but not listed in the Brewfile".to_string(),
+                    category: None,
+                    fallbacks: Vec::new(),
+                    app_id: item.app_id,
+                    version: None,
+                    options: BrewOptions::default(),
+                },
+                selected: false,
+                status: None,
+                excluded: None,
+                origin: Origin::Installed,
+                log: Vec::new(),
+                duration: None,
+            })
+        })
+        .collect()
+}
+
+/// Lower-cased name without its `owner/repo/` tap prefix, which is how
+/// `brew list` reports formulae and casks.
+fn short_name(name: &str) -> String {
+    name.rsplit('/').next().unwrap_or(name).to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_brewfile;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn states(drift: &[DriftItem]) -> Vec<(DriftState, &str)> {
+        drift
+            .iter()
+            .map(|item| (item.state, item.name.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn matches_names_regardless_of_case() {
+        let (items, _) = parse_brewfile(
+            "tap \"Owner/Tools\"\nbrew \"JQ\"\ncask \"Firefox\"\nvscode \"MS-Python.Python\"\n",
+        );
+        let inventory = Inventory {
+            taps: strings(&["owner/tools"]),
+            formulae: strings(&["jq"]),
+            installed_formulae: strings(&["jq", "oniguruma"]),
+            casks: strings(&["firefox"]),
+            vscode: strings(&["ms-python.python"]),
+            ..Inventory::default()
+        };
+        assert_eq!(
+            states(&compute(&items, &inventory)),
+            [
+                (DriftState::Installed, "Owner/Tools"),
+                (DriftState::Installed, "JQ"),
+                (DriftState::Installed, "Firefox"),
+                (DriftState::Installed, "MS-Python.Python"),
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_tap_qualified_names_by_short_name() {
+        let (items, _) = parse_brewfile("brew \"owner/tools/fmt\"\nbrew \"lint\"\n");
+        let inventory = Inventory {
+            taps: strings(&["owner/tools"]),
+            formulae: strings(&["fmt", "other/tap/lint", "other/tap/extra"]),
+            installed_formulae: strings(&["fmt", "lint", "extra"]),
+            ..Inventory::default()
+        };
+        assert_eq!(
+            states(&compute(&items, &inventory)),
+            [
+                (DriftState::Extra, "other/tap/extra"),
+                (DriftState::Installed, "owner/tools"),
+                (DriftState::Installed, "owner/tools/fmt"),
+                (DriftState::Installed, "lint"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unevaluated_entries_count_as_listed() {
+        let (mut items, _) = parse_brewfile("brew \"jq\"\nbrew \"rg\"\nbrew \"fd\"\n");
+        items[0].excluded = Some(brewfile::UNEVALUATED.to_string());
+        items[1].excluded = Some(brewfile::UNEVALUATED.to_string());
+        items[2].excluded = Some("Linux only".to_string());
+        let inventory = Inventory {
+            formulae: strings(&["jq", "fd"]),
+            installed_formulae: strings(&["jq", "fd"]),
+            ..Inventory::default()
+        };
+        // jq and rg may apply here, so neither is extra nor missing; fd is
+        // excluded outright, so having it installed is drift.
+        assert_eq!(
+            states(&compute(&items, &inventory)),
+            [(DriftState::Extra, "fd")]
+        );
+    }
+
+    #[test]
+    fn matches_app_store_apps_by_id() {
+        let (items, _) = parse_brewfile("mas \"Xcode\", id: 497799835\n");
+        let inventory = Inventory {
+            mas: vec![
+                ("497799835".to_string(), "Xcode Beta".to_string()),
+                ("409183694".to_string(), "Keynote".to_string()),
+            ],
+            ..Inventory::default()
+        };
+        let drift = compute(&items, &inventory);
+        assert_eq!(
+            states(&drift),
+            [
+                (DriftState::Extra, "Keynote"),
+                (DriftState::Installed, "Xcode"),
+            ]
+        );
+        assert_eq!(drift[0].app_id.as_deref(), Some("409183694"));
+        assert_eq!(
+            drift[0].entry_line.as_deref(),
+            Some("mas \"Keynote\", id: 409183694")
+        );
+    }
+
+    #[test]
+    fn cleanup_offers_only_removable_extras() {
+        let (items, _) = parse_brewfile("brew \"jq\"\n");
+        let inventory = Inventory {
+            taps: strings(&["owner/tools"]),
+            formulae: strings(&["jq", "wget"]),
+            installed_formulae: strings(&["jq", "wget"]),
+            casks: strings(&["firefox"]),
+            mas: vec![("409183694".to_string(), "Keynote".to_string())],
+            vscode: strings(&["rust-lang.rust-analyzer"]),
+            ..Inventory::default()
+        };
+        let candidates: Vec<(ToolKind, String, Option<String>)> =
+            cleanup_candidates(&items, &inventory)
+                .into_iter()
+                .map(|item| (item.tool.kind, item.tool.name, item.tool.app_id))
+                .collect();
+        assert_eq!(
+            candidates,
+            [
+                (ToolKind::BrewFormula, "wget".to_string(), None),
+                (ToolKind::BrewCask, "firefox".to_string(), None),
+                (
+                    ToolKind::Mas,
+                    "Keynote".to_string(),
+                    Some("409183694".to_string())
+                ),
+            ]
+        );
+    }
+}
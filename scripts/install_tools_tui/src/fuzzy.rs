@@ -0,0 +1,102 @@
+//! Fuzzy matching for the selection list's search.
+
+/// Scores how well `query` matches `text`, or returns `None` when the
+/// query's characters do not all appear in `text` in order. Matching
+/// ignores case; runs of consecutive characters and matches at the start of
+/// a word score higher, so `rg` ranks `ripgrep` below `rg` itself.
+pub fn score(query: &str, text: &str) -> Option<i32> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let bonus = |position: usize| {
+        if position == 0 || !text[position - 1].is_alphanumeric() {
+            4
+        } else {
+            1
+        }
+    };
+
+    // `best[position]` is the highest score for the query so far with its
+    // last character matched at `position`, so a later, better aligned
+    // match wins over the first one found.
+    let mut best: Vec<Option<i32>> = (0..text.len())
+        .map(|position| (text[position] == query[0]).then(|| bonus(position)))
+        .collect();
+    for &ch in &query[1..] {
+        let mut earlier: Option<i32> = None;
+        let mut next = vec![None; text.len()];
+        for position in 0..text.len() {
+            if position >= 2 {
+                earlier = earlier.max(best[position - 2]);
+            }
+            if text[position] != ch {
+                continue;
+            }
+            let adjacent = position
+                .checked_sub(1)
+                .and_then(|previous| best[previous])
+                .map(|score| score + 4);
+            next[position] = earlier.max(adjacent).map(|score| score + bonus(position));
+        }
+        best = next;
+    }
+    best.into_iter()
+        .flatten()
+        .max()
+        .map(|score| score - text.len() as i32 / 16)
+}
+
+/// Best score of `query` against any of `fields`.
+pub fn best(query: &str, fields: &[&str]) -> Option<i32> {
+    fields.iter().filter_map(|field| score(query, field)).max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_exact_names_above_longer_matches() {
+        assert_eq!(score("rg", "rg"), Some(9));
+        assert_eq!(score("rg", "ripgrep"), Some(5));
+        assert!(score("rg", "rg") > score("rg", "ripgrep"));
+    }
+
+    #[test]
+    fn ignores_case_and_whitespace_in_the_query() {
+        assert_eq!(score("RG", "ripGrep"), score("rg", "RIPGREP"));
+        assert_eq!(score("r g", "ripgrep"), score("rg", "ripgrep"));
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert_eq!(score("", "ripgrep"), Some(0));
+        assert_eq!(score("  ", ""), Some(0));
+    }
+
+    #[test]
+    fn rejects_characters_missing_or_out_of_order() {
+        assert_eq!(score("xyz", "ripgrep"), None);
+        assert_eq!(score("gr", "rg"), None);
+        assert_eq!(score("rg", ""), None);
+    }
+
+    #[test]
+    fn prefers_the_best_aligned_match() {
+        // A greedy match would take the `c` of `cask` and score 15.
+        assert_eq!(score("code", "cask-code"), Some(19));
+        assert_eq!(score("code", "cask-code"), score("code", "code-cask"));
+    }
+
+    #[test]
+    fn best_takes_the_highest_scoring_field() {
+        assert_eq!(best("rg", &["ripgrep", "rg"]), Some(9));
+        assert_eq!(best("zz", &["ripgrep", "rg"]), None);
+    }
+}
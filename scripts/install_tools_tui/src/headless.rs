@@ -0,0 +1,160 @@
+//! Installs a selection given on the command line without the interface,
+//! printing one line per event so the output reads well in logs.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+use crate::cli::{Format, Options};
+use crate::commands::{CommandError, print_json};
+use crate::pool::{self, JobEvent};
+use crate::process::{self, CancelToken};
+use crate::{
+    Operation, Status, Symbols, ToolState, format_duration, history, load_tools_from_brewfile,
+    log_entries, report, status_label, status_marker,
+};
+
+/// Installs the selected entries and returns whether every one succeeded.
+/// With `--format json` the progress lines go to stderr and the run's JSON
+/// report to stdout.
+pub fn run(options: &Options) -> Result<bool, CommandError> {
+    let json = options.format == Format::Json;
+    let say = |line: String| {
+        if json {
+            eprintln!("{line}");
+        } else {
+            println!("{line}");
+        }
+    };
+    let loaded = load_tools_from_brewfile(options.brewfile.as_deref())?;
+    if let Some(note) = &loaded.note {
+        eprintln!("{note}");
+    }
+    for diagnostic in &loaded.diagnostics {
+        eprintln!("Brewfile:{diagnostic}");
+    }
+    let mut items = loaded.items;
+    let selected = select(&mut items, options)?;
+
+    let operation = Operation::Install;
+    let symbols = Symbols::new();
+    let total = selected.len();
+    let jobs = selected
+        .iter()
+        .map(|&idx| (idx, items[idx].tool.clone()))
+        .collect();
+    let started = SystemTime::now();
+    let clock = Instant::now();
+    let mut started_at = HashMap::new();
+    let mut launched = 0;
+    let mut completed = 0;
+    say(format!(
+        "{} {total} of {} Brewfile entries.",
+        operation.progressive(),
+        items.len()
+    ));
+    // Ctrl-C or a CI timeout cancels the run, which then drains so the
+    // history and reports are still written.
+    let cancel = Arc::new(CancelToken::default());
+    process::cancel_on_signals(Arc::clone(&cancel));
+    for event in pool::spawn(jobs, operation, pool::worker_count(), Some(cancel)) {
+        match event {
+            JobEvent::Started(idx) => {
+                launched += 1;
+                started_at.insert(idx, Instant::now());
+                say(format!(
+                    "[{launched}/{total}] {} {}",
+                    operation.progressive(),
+                    items[idx].tool.label
+                ));
+            }
+            JobEvent::Log(idx, event) => items[idx].log.push(event),
+            JobEvent::Finished(idx, status) => {
+                completed += 1;
+                let item = &mut items[idx];
+                item.duration = started_at.remove(&idx).map(|start| start.elapsed());
+                say(format!(
+                    "[{completed}/{total}] {} {} {}: {}{}",
+                    status_marker(&status, &symbols, 0),
+                    item.tool.label,
+                    status_label(&status, &item.tool.kind, operation),
+                    status.message(),
+                    item.duration
+                        .map(|duration| format!(" ({})", format_duration(duration)))
+                        .unwrap_or_default()
+                ));
+                if matches!(status, Status::Failed(_)) {
+                    for (line, _) in log_entries(&item.log) {
+                        say(format!("    {line}"));
+                    }
+                }
+                item.status = Some(status);
+            }
+        }
+    }
+
+    let count = |name: &str| {
+        items
+            .iter()
+            .filter(|item| item.status.as_ref().map(history::status_name) == Some(name))
+            .count()
+    };
+    say(format!(
+        "Done in {}: {} succeeded, {} skipped, {} failed, {} cancelled.",
+        format_duration(clock.elapsed()),
+        count("success"),
+        count("skipped"),
+        count("failed"),
+        count("cancelled")
+    ));
+    match history::save(operation, started, &items) {
+        Ok(dir) => say(format!("Saved to {}.", dir.display())),
+        Err(err) => eprintln!("Run history not saved: {err}"),
+    }
+    for note in report::write_requested(options, operation, started, &items) {
+        say(note);
+    }
+    if json {
+        print_json(&report::json_report(operation, started, &items))?;
+    }
+    Ok(!report::any_failed(&items))
+}
+
+/// Marks the entries chosen by the selection flags and returns their
+/// indices. `--only` and `--kind` narrow the selection, `--exclude` removes
+/// from it, and `--all` is needed when neither narrows it.
+fn select(items: &mut [ToolState], options: &Options) -> Result<Vec<usize>, String> {
+    if !options.all && options.only.is_empty() && options.kinds.is_empty() {
+        return Err("Choose what to install with --all, --only or --kind".to_string());
+    }
+    for name in options.only.iter().chain(&options.exclude) {
+        if !items.iter().any(|item| matches_name(item, name)) {
+            return Err(format!("No Brewfile entry is named `{name}`"));
+        }
+    }
+    let mut selected = Vec::new();
+    for (idx, item) in items.iter_mut().enumerate() {
+        item.selected = item.excluded.is_none()
+            && (options.only.is_empty()
+                || options.only.iter().any(|name| matches_name(item, name)))
+            && (options.kinds.is_empty() || options.kinds.contains(&item.tool.kind))
+            && !options.exclude.iter().any(|name| matches_name(item, name));
+        if item.selected {
+            selected.push(idx);
+        }
+    }
+    if selected.is_empty() {
+        return Err("No Brewfile entries match the selection".to_string());
+    }
+    Ok(selected)
+}
+
+/// Matches an entry by its full name, its name without a tap prefix, or
+/// its App Store id, ignoring case.
+fn matches_name(item: &ToolState, name: &str) -> bool {
+    let tool = &item.tool;
+    let short = tool.name.rsplit('/').next().unwrap_or(&tool.name);
+    tool.name.eq_ignore_ascii_case(name)
+        || short.eq_ignore_ascii_case(name)
+        || tool.app_id.as_deref() == Some(name)
+}
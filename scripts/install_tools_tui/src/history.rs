@@ -0,0 +1,413 @@
+//! Run history kept on disk under `$XDG_STATE_HOME/install_tools_tui/`.
+//!
+//! Each run gets a directory named after its UTC start time holding
+//! `summary.json` (one record per item: tool, kind, commands, status,
+//! duration and exit code) and a raw `logs/<item>.log` per item.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{Value, json};
+
+use crate::brewfile::EntryKind;
+use crate::process::LogEvent;
+use crate::{
+    BrewOptions, Operation, Origin, Status, Tool, ToolKind, ToolState, exit_code_label,
+    format_duration,
+};
+
+const SUMMARY_FILE: &str = "summary.json";
+const LOGS_DIR: &str = "logs";
+
+/// A past run as listed in the history view.
+#[derive(Clone, Debug)]
+pub struct RunSummary {
+    pub dir: PathBuf,
+    pub started: String,
+    pub operation: Operation,
+    pub total: usize,
+    pub failed: usize,
+}
+
+/// `$XDG_STATE_HOME/install_tools_tui`, falling back to
+/// `~/.local/state/install_tools_tui`.
+pub fn state_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(base.join("install_tools_tui"))
+}
+
+/// Writes the items that took part in a run and returns its directory.
+pub fn save(
+    operation: Operation,
+    started: SystemTime,
+    items: &[ToolState],
+) -> Result<PathBuf, String> {
+    let root = state_dir().ok_or("Cannot locate a state directory; HOME is not set")?;
+    let dir = unique_dir(&root, &timestamp(started));
+    let logs = dir.join(LOGS_DIR);
+    fs::create_dir_all(&logs)
+        .map_err(|err| format!("Failed to create {}: {err}", logs.display()))?;
+
+    let mut records = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        if item.status.is_none() {
+            continue;
+        }
+        let log_name = format!("{:03}-{}.log", index + 1, file_safe(&item.tool.name));
+        let log_path = logs.join(&log_name);
+        fs::write(&log_path, render_log(&item.log))
+            .map_err(|err| format!("Failed to write {}: {err}", log_path.display()))?;
+
+        let mut record = item_json(item);
+        record["log"] = json!(format!("{LOGS_DIR}/{log_name}"));
+        records.push(record);
+    }
+
+    let summary = json!({
+        "started": timestamp(started),
+        "operation": operation.name(),
+        "items": records,
+    });
+    let text = serde_json::to_string_pretty(&summary)
+        .map_err(|err| format!("Failed to encode run summary: {err}"))?;
+    let path = dir.join(SUMMARY_FILE);
+    fs::write(&path, text).map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+    Ok(dir)
+}
+
+/// Past runs, newest first. Directories without a readable summary are
+/// skipped.
+pub fn list() -> Vec<RunSummary> {
+    let Some(root) = state_dir() else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(&root) else {
+        return Vec::new();
+    };
+    let mut runs: Vec<RunSummary> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let dir = entry.path();
+            let summary = read_summary(&dir).ok()?;
+            let items = summary.get("items").and_then(Value::as_array)?;
+            Some(RunSummary {
+                started: str_field(&summary, "started"),
+                operation: Operation::from_name(&str_field(&summary, "operation"))?,
+                total: items.len(),
+                failed: items
+                    .iter()
+                    .filter(|item| str_field(item, "status") == "failed")
+                    .count(),
+                dir,
+            })
+        })
+        .collect();
+    runs.sort_by(|a, b| b.dir.cmp(&a.dir));
+    runs
+}
+
+/// Reads a saved run back as result items, with statuses and logs.
+pub fn load(dir: &Path) -> Result<Vec<ToolState>, String> {
+    let summary = read_summary(dir)?;
+    let items = summary
+        .get("items")
+        .and_then(Value::as_array)
+        .ok_or_else(|| format!("{} has no items", dir.join(SUMMARY_FILE).display()))?;
+    Ok(items
+        .iter()
+        .map(|item| {
+            let kind = EntryKind::from_keyword(&str_field(item, "kind"))
+                .map_or(ToolKind::BrewFormula, ToolKind::from_entry_kind);
+            let message = str_field(item, "message");
+            let log = fs::read_to_string(dir.join(str_field(item, "log")))
+                .map(|text| parse_log(&text))
+                .unwrap_or_default();
+            ToolState {
+                tool: Tool {
+                    kind,
+                    name: str_field(item, "tool"),
+                    label: str_field(item, "label"),
+                    description: String::new(),
+                    category: None,
+                    fallbacks: Vec::new(),
+                    app_id: item
+                        .get("app_id")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                    version: None,
+                    options: BrewOptions::default(),
+                },
+                selected: true,
+                status: Some(parse_status(&str_field(item, "status"), message)),
+                excluded: None,
+                origin: Origin::Installed,
+                log,
+                duration: item
+                    .get("duration_secs")
+                    .and_then(Value::as_f64)
+                    .map(Duration::from_secs_f64),
+            }
+        })
+        .collect())
+}
+
+/// The record kept for an item in summaries and reports: what it is, the
+/// commands it ran, and how it ended.
+pub fn item_json(item: &ToolState) -> Value {
+    let commands: Vec<Value> = commands(&item.log)
+        .into_iter()
+        .map(|(command, code, duration)| {
+            json!({
+                "command": command,
+                "exit_code": code,
+                "duration_secs": duration.map(|duration| duration.as_secs_f64()),
+            })
+        })
+        .collect();
+    let last = commands.last();
+    json!({
+        "tool": item.tool.name,
+        "label": item.tool.label,
+        "kind": item.tool.kind.entry_kind().keyword(),
+        "app_id": item.tool.app_id,
+        "command": last.and_then(|command| command.get("command")).cloned(),
+        "exit_code": last.and_then(|command| command.get("exit_code")).cloned(),
+        "status": item.status.as_ref().map(status_name),
+        "message": item.status.as_ref().map(Status::message),
+        "duration_secs": item.duration.map(|duration| duration.as_secs_f64()),
+        "commands": commands,
+    })
+}
+
+fn read_summary(dir: &Path) -> Result<Value, String> {
+    let path = dir.join(SUMMARY_FILE);
+    let text = fs::read_to_string(&path)
+        .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+    serde_json::from_str(&text).map_err(|err| format!("Failed to parse {}: {err}", path.display()))
+}
+
+fn str_field(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+pub fn status_name(status: &Status) -> &'static str {
+    match status {
+        Status::Pending(_) => "pending",
+        Status::Success(_) => "success",
+        Status::Skipped(_) => "skipped",
+        Status::Failed(_) => "failed",
+        Status::Cancelled(_) => "cancelled",
+    }
+}
+
+fn parse_status(name: &str, message: String) -> Status {
+    match name {
+        "success" => Status::Success(message),
+        "skipped" => Status::Skipped(message),
+        "cancelled" => Status::Cancelled(message),
+        "pending" => Status::Pending(message),
+        _ => Status::Failed(message),
+    }
+}
+
+/// Each command with its exit code and duration, in the order they ran.
+fn commands(log: &[LogEvent]) -> Vec<(String, Option<i32>, Option<Duration>)> {
+    let mut commands: Vec<(String, Option<i32>, Option<Duration>)> = Vec::new();
+    for event in log {
+        match event {
+            LogEvent::Command(command) => commands.push((command.clone(), None, None)),
+            LogEvent::Exit { code, duration } => {
+                if let Some(last) = commands.last_mut() {
+                    last.1 = *code;
+                    last.2 = Some(*duration);
+                }
+            }
+            LogEvent::Output { .. } => {}
+        }
+    }
+    commands
+}
+
+/// The log as plain text: `$ command`, its output, then an `exit` line.
+fn render_log(log: &[LogEvent]) -> String {
+    let mut text = String::new();
+    for event in log {
+        let line = match event {
+            LogEvent::Command(command) => format!("$ {command}"),
+            LogEvent::Output { line, .. } => line.clone(),
+            LogEvent::Exit { code, duration } => format!(
+                "exit {} after {}",
+                exit_code_label(*code),
+                format_duration(*duration)
+            ),
+        };
+        text.push_str(&line);
+        text.push('\n');
+    }
+    text
+}
+
+fn parse_log(text: &str) -> Vec<LogEvent> {
+    text.lines()
+        .map(|line| {
+            if let Some(command) = line.strip_prefix("$ ") {
+                return LogEvent::Command(command.to_string());
+            }
+            if let Some(exit) = parse_exit_line(line) {
+                return exit;
+            }
+            LogEvent::Output {
+                line: line.to_string(),
+                stderr: false,
+            }
+        })
+        .collect()
+}
+
+fn parse_exit_line(line: &str) -> Option<LogEvent> {
+    let rest = line.strip_prefix("exit ")?;
+    let (code, seconds) = rest.split_once(" after ")?;
+    let seconds: f64 = seconds.strip_suffix('s')?.parse().ok()?;
+    let code = match code {
+        "signal" => None,
+        code => Some(code.parse().ok()?),
+    };
+    Some(LogEvent::Exit {
+        code,
+        duration: Duration::from_secs_f64(seconds),
+    })
+}
+
+fn unique_dir(root: &Path, name: &str) -> PathBuf {
+    let mut dir = root.join(name);
+    let mut suffix = 2;
+    while dir.exists() {
+        dir = root.join(format!("{name}-{suffix}"));
+        suffix += 1;
+    }
+    dir
+}
+
+fn file_safe(name: &str) -> String {
+    name.chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.') {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// UTC time as `2024-05-01T09-30-00Z`, which sorts chronologically and is
+/// safe in file names.
+pub fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (hour, minute, second) = (rem / 3600, rem % 3600 / 60, rem % 60);
+
+    // Civil-from-days, after Howard Hinnant's date algorithms.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}T{hour:02}-{minute:02}-{second:02}Z")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn formats_known_epochs() {
+        assert_eq!(timestamp(at(0)), "1970-01-01T00-00-00Z");
+        assert_eq!(timestamp(at(951_782_400)), "2000-02-29T00-00-00Z");
+        assert_eq!(timestamp(at(951_868_799)), "2000-02-29T23-59-59Z");
+        assert_eq!(timestamp(at(1_798_761_599)), "2026-12-31T23-59-59Z");
+    }
+
+    #[test]
+    fn log_survives_a_write_and_read() {
+        let log = vec![
+            LogEvent::Command("brew install jq".to_string()),
+            LogEvent::Output {
+                line: "==> Pouring jq".to_string(),
+                stderr: false,
+            },
+            LogEvent::Output {
+                line: "Warning: jq 1.7.1 is already installed".to_string(),
+                stderr: true,
+            },
+            LogEvent::Exit {
+                code: Some(1),
+                duration: Duration::from_millis(2500),
+            },
+            LogEvent::Command("brew install --force jq".to_string()),
+            LogEvent::Exit {
+                code: None,
+                duration: Duration::from_secs(61),
+            },
+        ];
+        let text = render_log(&log);
+        assert_eq!(
+            text,
+            "$ brew install jq\n==> Pouring jq\nWarning: jq 1.7.1 is already installed\n\
+             exit 1 after 2.5s\n$ brew install --force jq\nexit signal after 61.0s\n"
+        );
+
+        let parsed = parse_log(&text);
+        assert_eq!(parsed.len(), log.len());
+        assert!(matches!(&parsed[0], LogEvent::Command(command) if command == "brew install jq"));
+        // The saved log is plain text, so stderr reads back as ordinary output.
+        assert!(matches!(
+            &parsed[2],
+            LogEvent::Output { line, stderr: false } if line == "Warning: jq 1.7.1 is already installed"
+        ));
+        assert_eq!(
+            commands(&parsed),
+            [
+                (
+                    "brew install jq".to_string(),
+                    Some(1),
+                    Some(Duration::from_millis(2500))
+                ),
+                (
+                    "brew install --force jq".to_string(),
+                    None,
+                    Some(Duration::from_secs(61))
+                ),
+            ]
+        );
+        assert_eq!(render_log(&parsed), text);
+    }
+
+    #[test]
+    fn rejects_malformed_exit_lines() {
+        assert!(parse_exit_line("exit 0 after 1.0s").is_some());
+        assert!(parse_exit_line("exit 0").is_none());
+        assert!(parse_exit_line("exit 0 after soon").is_none());
+        assert!(parse_exit_line("exit code after 1.0s").is_none());
+        assert!(parse_exit_line("exited 0 after 1.0s").is_none());
+    }
+}
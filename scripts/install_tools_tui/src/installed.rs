@@ -0,0 +1,121 @@
+//! What the Brewfile's entries look like on this machine before anything
+//! is installed, probed in the background with one listing per tool.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::inventory::{command_lines, parse_mas_line};
+use crate::{
+    Tool, ToolKind, brew_available, code_command, go_bin_dir, go_binary_name, is_builtin_tap,
+    mas_available,
+};
+
+/// Installed software and versions, keyed by lower-cased name (formulae and
+/// casks without their tap prefix) or App Store id.
+#[derive(Clone, Debug, Default)]
+pub struct Installed {
+    taps: HashSet<String>,
+    formulae: HashMap<String, String>,
+    casks: HashMap<String, String>,
+    mas: HashMap<String, String>,
+    vscode: HashMap<String, String>,
+    /// Listings that could not be taken; their entries count as missing.
+    pub warnings: Vec<String>,
+}
+
+/// Probes on a background thread; the channel yields the result once.
+pub fn spawn() -> Receiver<Installed> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(Installed::probe());
+    });
+    receiver
+}
+
+impl Installed {
+    pub fn probe() -> Self {
+        let mut installed = Installed::default();
+        let mut lines = |args: &[&str]| match command_lines(args) {
+            Ok(lines) => lines,
+            Err(err) => {
+                installed.warnings.push(err);
+                Vec::new()
+            }
+        };
+
+        let (mut taps, mut formulae, mut casks, mut mas, mut vscode) =
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        if brew_available() {
+            taps = lines(&["brew", "tap"]);
+            formulae = lines(&["brew", "list", "--formula", "--versions"]);
+            casks = lines(&["brew", "list", "--cask", "--versions"]);
+        }
+        if mas_available() {
+            mas = lines(&["mas", "list"]);
+        }
+        if let Some(code) = code_command() {
+            vscode = lines(&[&code, "--list-extensions", "--show-versions"]);
+        }
+
+        installed.taps = taps.iter().map(|tap| tap.to_ascii_lowercase()).collect();
+        installed.formulae = formulae
+            .iter()
+            .filter_map(|line| name_version(line))
+            .collect();
+        installed.casks = casks.iter().filter_map(|line| name_version(line)).collect();
+        installed.mas = mas
+            .iter()
+            .filter_map(|line| {
+                let (id, _) = parse_mas_line(line)?;
+                Some((id, mas_version(line).unwrap_or_default()))
+            })
+            .collect();
+        installed.vscode = vscode
+            .iter()
+            .map(|line| match line.split_once('@') {
+                Some((name, version)) => (name.to_ascii_lowercase(), version.to_string()),
+                None => (line.to_ascii_lowercase(), String::new()),
+            })
+            .collect();
+        installed
+    }
+
+    /// The installed version of `tool`, empty when it is installed but the
+    /// listing gives no version, or `None` when it is missing.
+    pub fn version(&self, tool: &Tool) -> Option<String> {
+        let name = tool.name.to_ascii_lowercase();
+        let short = name.rsplit('/').next().unwrap_or(&name);
+        match tool.kind {
+            ToolKind::Tap => {
+                (is_builtin_tap(&tool.name) || self.taps.contains(&name)).then(String::new)
+            }
+            ToolKind::BrewFormula => self.formulae.get(short).cloned(),
+            ToolKind::BrewCask => self.casks.get(short).cloned(),
+            ToolKind::Mas => tool
+                .app_id
+                .as_ref()
+                .and_then(|id| self.mas.get(id))
+                .cloned(),
+            ToolKind::VsCode => self.vscode.get(&name).cloned(),
+            ToolKind::Go => go_bin_dir()
+                .is_some_and(|dir| dir.join(go_binary_name(&tool.name)).is_file())
+                .then(String::new),
+        }
+    }
+}
+
+/// Parses a `brew list --versions` line such as `python@3.12 3.12.4 3.12.3`,
+/// keeping the first version listed.
+fn name_version(line: &str) -> Option<(String, String)> {
+    let mut words = line.split_whitespace();
+    let name = words.next()?.to_ascii_lowercase();
+    Some((name, words.next().unwrap_or_default().to_string()))
+}
+
+/// The version in parentheses at the end of a `mas list` line.
+fn mas_version(line: &str) -> Option<String> {
+    let line = line.trim().strip_suffix(')')?;
+    let start = line.rfind('(')?;
+    Some(line[start + 1..].trim().to_string())
+}
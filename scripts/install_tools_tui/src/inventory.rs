@@ -0,0 +1,202 @@
+//! Inspection of what is actually installed on this machine, and export of
+//! that state as a Brewfile.
+
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::brewfile::{self, Document, EntryKind, OptionValue};
+use crate::{brew_available, code_command, is_builtin_tap, mas_available, run_command};
+
+/// Software found on the machine, grouped by Brewfile entry kind.
+#[derive(Clone, Debug, Default)]
+pub struct Inventory {
+    pub taps: Vec<String>,
+    /// Formulae installed on request that nothing else depends on.
+    pub formulae: Vec<String>,
+    /// Every installed formula, dependencies included.
+    pub installed_formulae: Vec<String>,
+    pub casks: Vec<String>,
+    /// `(id, name)` pairs from `mas list`.
+    pub mas: Vec<(String, String)>,
+    pub vscode: Vec<String>,
+    /// Tools that were missing or failed, so the caller can report them.
+    pub warnings: Vec<String>,
+}
+
+/// Collects on a background thread; the channel yields the result once.
+pub fn spawn() -> Receiver<Inventory> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(Inventory::collect());
+    });
+    receiver
+}
+
+impl Inventory {
+    pub fn collect() -> Self {
+        let mut inventory = Inventory::default();
+
+        if brew_available() {
+            match command_lines(&["brew", "tap"]) {
+                Ok(taps) => {
+                    inventory.taps = taps
+                        .into_iter()
+                        .filter(|tap| !is_builtin_tap(tap))
+                        .collect()
+                }
+                Err(err) => inventory.warnings.push(err),
+            }
+            match command_lines(&["brew", "leaves"]) {
+                Ok(formulae) => inventory.formulae = formulae,
+                Err(err) => inventory.warnings.push(err),
+            }
+            match command_lines(&["brew", "list", "--formula", "-1"]) {
+                Ok(formulae) => inventory.installed_formulae = formulae,
+                Err(err) => inventory.warnings.push(err),
+            }
+            match command_lines(&["brew", "list", "--cask", "-1"]) {
+                Ok(casks) => inventory.casks = casks,
+                Err(err) => inventory.warnings.push(err),
+            }
+        } else {
+            inventory
+                .warnings
+                .push("Homebrew not available; skipped taps, formulae and casks".to_string());
+        }
+
+        if mas_available() {
+            match command_lines(&["mas", "list"]) {
+                Ok(lines) => {
+                    inventory.mas = lines
+                        .iter()
+                        .filter_map(|line| parse_mas_line(line))
+                        .collect()
+                }
+                Err(err) => inventory.warnings.push(err),
+            }
+        } else {
+            inventory
+                .warnings
+                .push("mas CLI not available; skipped App Store apps".to_string());
+        }
+
+        match code_command() {
+            Some(code) => match command_lines(&[&code, "--list-extensions"]) {
+                Ok(extensions) => inventory.vscode = extensions,
+                Err(err) => inventory.warnings.push(err),
+            },
+            None => inventory
+                .warnings
+                .push("VS Code CLI (code) not available; skipped extensions".to_string()),
+        }
+
+        inventory
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.taps.len()
+            + self.formulae.len()
+            + self.casks.len()
+            + self.mas.len()
+            + self.vscode.len()
+    }
+
+    /// Renders the inventory as a Brewfile in the same shape as a
+    /// hand-maintained one: taps first, then formulae and casks each with a
+    /// `# description` comment above (which `parse_brewfile` reads back),
+    /// then App Store apps and VS Code extensions.
+    pub fn to_brewfile(&self) -> String {
+        let formula_descriptions = brew_descriptions("--formula", &self.formulae);
+        let cask_descriptions = brew_descriptions("--cask", &self.casks);
+
+        let mut document = Document::new("", &[]);
+        let mut add =
+            |kind, name: &str, options: &[(String, OptionValue)], description: Option<&String>| {
+                let line = brewfile::format_entry(kind, name, options);
+                document.insert_entry(kind, &line, description.map(String::as_str));
+            };
+        for tap in &self.taps {
+            add(EntryKind::Tap, tap, &[], None);
+        }
+        for formula in &self.formulae {
+            add(
+                EntryKind::Brew,
+                formula,
+                &[],
+                formula_descriptions.get(formula),
+            );
+        }
+        for cask in &self.casks {
+            add(EntryKind::Cask, cask, &[], cask_descriptions.get(cask));
+        }
+        for (id, name) in &self.mas {
+            let options = [("id".to_string(), OptionValue::Number(id.clone()))];
+            add(EntryKind::Mas, name, &options, None);
+        }
+        for extension in &self.vscode {
+            add(EntryKind::Vscode, extension, &[], None);
+        }
+        document.render()
+    }
+}
+
+pub fn command_lines(args: &[&str]) -> Result<Vec<String>, String> {
+    match run_command(args) {
+        Ok(output) if output.status.success() => Ok(output
+            .stdout
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()),
+        Ok(output) => Err(format!(
+            "{} failed: {}",
+            args.join(" "),
+            output.failure_message()
+        )),
+        Err(err) => Err(format!("Failed to run {}: {err}", args.join(" "))),
+    }
+}
+
+/// Parses a `mas list` line such as `497799835  Xcode  (15.0)`.
+pub fn parse_mas_line(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    let (id, rest) = line.split_once(char::is_whitespace)?;
+    if id.is_empty() || !id.chars().all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    let rest = rest.trim();
+    let name = match rest.rfind(" (") {
+        Some(index) if rest.ends_with(')') => &rest[..index],
+        _ => rest,
+    };
+    Some((id.to_string(), name.trim().to_string()))
+}
+
+/// Looks up one-line descriptions with `brew desc`, keyed by name.
+pub fn brew_descriptions(flag: &str, names: &[String]) -> HashMap<String, String> {
+    if names.is_empty() {
+        return HashMap::new();
+    }
+    let mut args = vec!["brew", "desc", flag];
+    args.extend(names.iter().map(String::as_str));
+    let Ok(output) = run_command(&args) else {
+        return HashMap::new();
+    };
+    output
+        .stdout
+        .lines()
+        .filter_map(|line| {
+            let (name, description) = line.split_once(": ")?;
+            // Casks are printed as `name: (App Name) description`.
+            let description = match description.strip_prefix('(') {
+                Some(rest) => rest.split_once(") ").map_or(rest, |(_, desc)| desc),
+                None => description,
+            };
+            let description = description.trim();
+            (!description.is_empty() && description != "None")
+                .then(|| (name.trim().to_string(), description.to_string()))
+        })
+        .collect()
+}
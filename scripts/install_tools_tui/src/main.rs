@@ -1,12 +1,14 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::{self, stdout};
+use std::io::{self, IsTerminal, stdout};
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::time::Duration;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
 
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
@@ -19,20 +21,84 @@ use ratatui::{
 use reqwest::blocking::Client;
 use which::which;
 
+mod brewfile;
+mod cli;
+mod commands;
+mod details;
+mod drift;
+mod fuzzy;
+mod headless;
+mod history;
+mod installed;
+mod inventory;
+mod outdated;
+mod pool;
+mod process;
+mod report;
+
+use brewfile::{Diagnostic, Document, Entry, EntryKind, Host, OptionValue, Severity};
+use details::{Details, Lookup};
+use drift::{DriftItem, DriftState};
+use history::RunSummary;
+use installed::Installed;
+use inventory::Inventory;
+use outdated::Outdated;
+use pool::JobEvent;
+use process::{CancelToken, LogEvent};
+
 const BREWFILE_SOURCE_ENV: &str = "BREWFILE_SOURCE";
 const BREWFILE_PATH_ENV: &str = "BREWFILE_PATH";
 const BREWFILE_URL_ENV: &str = "BREWFILE_URL";
+const VSCODE_BUNDLED_CLI: &str =
+    "/Applications/Visual Studio Code.app/Contents/Resources/app/bin/code";
+const DEFAULT_EXPORT_PATH: &str = "Brewfile.dump";
+/// File the selection is saved to by default, next to the loaded Brewfile.
+const DEFAULT_SAVE_NAME: &str = "Brewfile.selection";
+/// Lines moved by PageUp/PageDown in the output pane.
+const LOG_PAGE: usize = 10;
+/// How long the cursor rests on an entry before its details are fetched,
+/// so scrolling past entries does not start a lookup for each.
+const DETAIL_DELAY: Duration = Duration::from_millis(300);
 const DEFAULT_BREWFILE_URL: &str = "https://raw.githubusercontent.com/isaaclins/dotfiles/HEAD/Brewfile";
 
+/// Exit status when the run finished but some items failed or were cancelled.
+const EXIT_ITEMS_FAILED: i32 = 2;
+
 fn main() {
-    if let Err(err) = run() {
-        eprintln!("Error: {err}");
-        std::process::exit(1);
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            std::process::exit(1);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    let result = match options.resolved_command(stdout().is_terminal()) {
+        cli::Command::Tui => run(options),
+        cli::Command::Install => headless::run(&options).map_err(io::Error::from),
+        _ => commands::run(&options).map_err(io::Error::from),
+    };
+    match result {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(EXIT_ITEMS_FAILED),
+        // The reader stopped early, as `head` does; that is not an error.
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(err) => {
+            eprintln!("Error: {err}");
+            std::process::exit(1);
+        }
     }
 }
 
-fn run() -> io::Result<()> {
-    let mut app = App::new().map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+/// Runs the interface and reports whether every item of the last operation
+/// succeeded.
+fn run(options: cli::Options) -> io::Result<bool> {
+    let mut app = App::new(options.brewfile.as_deref()).map_err(io::Error::other)?;
+    app.options = options;
+    app.start_probe();
 
     enable_raw_mode()?;
     let mut stdout = stdout();
@@ -40,33 +106,50 @@ fn run() -> io::Result<()> {
     let backend = ratatui::prelude::CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
+    // Leave the terminal usable if anything panics while in raw mode.
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+        default_hook(info);
+    }));
+
     let result = run_app(&mut terminal, &mut app);
+    if let Some(cancel) = &app.cancel {
+        cancel.cancel();
+    }
 
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
 
-    result
+    result.map(|()| !app.run_failed)
 }
 
 fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
     loop {
+        app.poll_jobs();
+        app.poll_probe();
+        app.poll_listing();
+        app.poll_details();
+        app.tick = app.tick.wrapping_add(1);
         terminal.draw(|frame| draw(frame, app))?;
         if app.should_quit {
             break;
         }
 
-        if event::poll(Duration::from_millis(150))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind != KeyEventKind::Press {
-                    continue;
-                }
-                match app.handle_key(key) {
-                    Action::None => {}
-                    Action::StartInstall => {
-                        perform_installations(terminal, app)?;
-                    }
-                }
+        // Poll faster while jobs run so the spinner keeps moving.
+        let timeout = if app.jobs.is_some() { 100 } else { 150 };
+        if event::poll(Duration::from_millis(timeout))?
+            && let Event::Key(key) = event::read()?
+        {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match app.handle_key(key) {
+                Action::None => {}
+                Action::StartInstall => app.start_operation(),
+                action => app.start_listing(action),
             }
         }
     }
@@ -79,15 +162,143 @@ struct Tool {
     name: String,
     label: String,
     description: String,
+    /// Section-header comment the entry sits under in the Brewfile.
+    category: Option<String>,
     fallbacks: Vec<String>,
     app_id: Option<String>,
+    /// Go module version, or the version an upgrade moves to.
+    version: Option<String>,
+    options: BrewOptions,
+}
+
+/// Per-entry options from the Ruby hash trailing a `brew`/`cask` line,
+/// e.g. `brew "hugo", link: false`.
+#[derive(Clone, Debug, Default)]
+struct BrewOptions {
+    link: Option<bool>,
+    args: Vec<String>,
+    restart_service: Option<ServiceRestart>,
+    start_service: bool,
+    greedy: bool,
+    /// Clone URL given as the second argument of a `tap` entry.
+    tap_url: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ServiceRestart {
+    Always,
+    Changed,
+}
+
+impl BrewOptions {
+    fn from_entry(entry: &Entry) -> Self {
+        let mut options = BrewOptions::default();
+        for (key, value) in &entry.options {
+            match (key.as_str(), value) {
+                ("link", value) => options.link = value.as_bool(),
+                ("args", value) => options.args = value.as_cli_args(),
+                ("restart_service", OptionValue::Symbol(sym)) if sym == "changed" => {
+                    options.restart_service = Some(ServiceRestart::Changed);
+                }
+                ("restart_service", value) => {
+                    options.restart_service =
+                        (value.as_bool() == Some(true)).then_some(ServiceRestart::Always);
+                }
+                ("start_service", value) => options.start_service = value.as_bool() == Some(true),
+                ("greedy", value) => options.greedy = value.as_bool() == Some(true),
+                _ => {}
+            }
+        }
+        options
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 enum ToolKind {
+    Tap,
     BrewFormula,
     BrewCask,
     Mas,
+    VsCode,
+    Go,
+}
+
+impl ToolKind {
+    fn from_entry_kind(kind: EntryKind) -> Self {
+        match kind {
+            EntryKind::Tap => ToolKind::Tap,
+            EntryKind::Brew => ToolKind::BrewFormula,
+            EntryKind::Cask => ToolKind::BrewCask,
+            EntryKind::Mas => ToolKind::Mas,
+            EntryKind::Vscode => ToolKind::VsCode,
+            EntryKind::Go => ToolKind::Go,
+        }
+    }
+
+    fn entry_kind(&self) -> EntryKind {
+        match self {
+            ToolKind::Tap => EntryKind::Tap,
+            ToolKind::BrewFormula => EntryKind::Brew,
+            ToolKind::BrewCask => EntryKind::Cask,
+            ToolKind::Mas => EntryKind::Mas,
+            ToolKind::VsCode => EntryKind::Vscode,
+            ToolKind::Go => EntryKind::Go,
+        }
+    }
+
+    /// Every kind, in the order the selection list shows its section.
+    const ALL: [ToolKind; 6] = [
+        ToolKind::Tap,
+        ToolKind::BrewFormula,
+        ToolKind::BrewCask,
+        ToolKind::Mas,
+        ToolKind::VsCode,
+        ToolKind::Go,
+    ];
+
+    fn section_title(&self) -> &'static str {
+        match self {
+            ToolKind::Tap => "Taps",
+            ToolKind::BrewFormula => "Homebrew formulae",
+            ToolKind::BrewCask => "Homebrew casks",
+            ToolKind::Mas => "App Store apps",
+            ToolKind::VsCode => "VS Code extensions",
+            ToolKind::Go => "Go tools",
+        }
+    }
+}
+
+/// A group of the selection list: every entry of a kind, or every entry
+/// under a Brewfile section-header comment (`None` for those under none).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Section {
+    Kind(ToolKind),
+    Category(Option<String>),
+}
+
+impl Section {
+    fn title(&self) -> &str {
+        match self {
+            Section::Kind(kind) => kind.section_title(),
+            Section::Category(Some(category)) => category,
+            Section::Category(None) => "Uncategorized",
+        }
+    }
+
+    fn contains(&self, tool: &Tool) -> bool {
+        match self {
+            Section::Kind(kind) => tool.kind == *kind,
+            Section::Category(category) => tool.category == *category,
+        }
+    }
+}
+
+/// A line of the selection list: a section header or one of its items.
+#[derive(Clone, Debug, PartialEq)]
+enum Row {
+    Section(Section),
+    /// Index into `App::items`.
+    Item(usize),
 }
 
 #[derive(Clone, Debug)]
@@ -95,6 +306,27 @@ struct ToolState {
     tool: Tool,
     selected: bool,
     status: Option<Status>,
+    /// Why the entry does not apply to this host (from an `if`/`unless` in
+    /// the Brewfile). Such entries are shown but cannot be selected.
+    excluded: Option<String>,
+    origin: Origin,
+    /// Commands run for this item in the last operation, with their output.
+    log: Vec<LogEvent>,
+    /// Wall time of the last operation on this item, once finished.
+    duration: Option<Duration>,
+}
+
+/// Where a tool in the list came from, so selections can be written back.
+#[derive(Clone, Debug)]
+enum Origin {
+    /// An entry on this line of the loaded Brewfile.
+    Line(usize),
+    /// A tap implied by a fully qualified formula or cask name.
+    Implied,
+    /// An entry added from the TUI, with its Brewfile line.
+    Added(String),
+    /// Software found on the machine that the Brewfile does not list.
+    Installed,
 }
 
 #[derive(Clone, Debug)]
@@ -103,6 +335,8 @@ enum Status {
     Success(String),
     Skipped(String),
     Failed(String),
+    /// Stopped by the user, either while running or before it started.
+    Cancelled(String),
 }
 
 impl Status {
@@ -111,7 +345,8 @@ impl Status {
             Status::Pending(msg)
             | Status::Success(msg)
             | Status::Skipped(msg)
-            | Status::Failed(msg) => msg,
+            | Status::Failed(msg)
+            | Status::Cancelled(msg) => msg,
         }
     }
 }
@@ -121,18 +356,91 @@ enum Mode {
     Selecting,
     Confirm,
     Results,
+    Diagnostics,
+    Save,
+    AddEntry,
+    Export,
+    Drift,
+    Cleanup,
+    Upgrade,
+    Log,
+    History,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Action {
     None,
     StartInstall,
+    Export,
+    Drift,
+    Cleanup,
+    Upgrade,
+}
+
+/// A listing of installed software being taken on a background thread,
+/// and what it is for.
+enum Listing {
+    Inventory(InventoryUse, Receiver<Inventory>),
+    Outdated(Receiver<Outdated>),
+}
+
+enum InventoryUse {
+    /// Write it as a Brewfile to this path.
+    Export(PathBuf),
+    Drift,
+    Cleanup,
+}
+
+/// What the Confirm and Results screens do with the selected items.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operation {
+    Install,
+    Uninstall,
+    Upgrade,
+}
+
+impl Operation {
+    fn verb(self) -> &'static str {
+        match self {
+            Operation::Install => "Install",
+            Operation::Uninstall => "Remove",
+            Operation::Upgrade => "Upgrade",
+        }
+    }
+
+    fn progressive(self) -> &'static str {
+        match self {
+            Operation::Install => "Installing",
+            Operation::Uninstall => "Removing",
+            Operation::Upgrade => "Upgrading",
+        }
+    }
+
+    /// Stable name used in saved run summaries.
+    fn name(self) -> &'static str {
+        match self {
+            Operation::Install => "install",
+            Operation::Uninstall => "uninstall",
+            Operation::Upgrade => "upgrade",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "install" => Some(Operation::Install),
+            "uninstall" => Some(Operation::Uninstall),
+            "upgrade" => Some(Operation::Upgrade),
+            _ => None,
+        }
+    }
 }
 
 struct Symbols {
     success: &'static str,
     failure: &'static str,
-    pending: &'static str,
+    cancelled: &'static str,
+    /// Frames drawn in turn next to items that are still running.
+    spinner: &'static [&'static str],
 }
 
 impl Symbols {
@@ -141,55 +449,243 @@ impl Symbols {
             Symbols {
                 success: "[OK]",
                 failure: "[X]",
-                pending: "[...]",
+                cancelled: "[-]",
+                spinner: &["[|]", "[/]", "[-]", "[\\]"],
             }
         } else {
             Symbols {
                 success: "[✅]",
                 failure: "[❌]",
-                pending: "[…]",
+                cancelled: "[⛔]",
+                spinner: &[
+                    "[⠋]", "[⠙]", "[⠹]", "[⠸]", "[⠼]", "[⠴]", "[⠦]", "[⠧]", "[⠇]", "[⠏]",
+                ],
             }
         }
     }
 }
 
+/// Identifies an entry in the detail cache.
+type DetailKey = (ToolKind, String);
+
+fn detail_key(tool: &Tool) -> DetailKey {
+    (tool.kind.clone(), tool.name.clone())
+}
+
 struct App {
     items: Vec<ToolState>,
+    /// Position of the cursor in `rows`.
     index: usize,
+    /// Indices into `items` that match `filter`, best match first, or every
+    /// item in Brewfile order when there is no filter.
+    visible: Vec<usize>,
+    /// The visible items grouped under a header per section, leaving out
+    /// the items of collapsed sections.
+    rows: Vec<Row>,
+    collapsed: HashSet<Section>,
+    /// Group by the Brewfile's section-header comments rather than by kind.
+    by_category: bool,
+    /// What is already installed, once the background probe has reported.
+    installed: Option<Installed>,
+    probe: Option<Receiver<Installed>>,
+    /// The export, drift, cleanup or upgrade listing being taken, if any.
+    listing: Option<Listing>,
+    /// Whether the selection screen shows the detail pane.
+    show_details: bool,
+    /// Details looked up so far, by kind and name.
+    details: HashMap<DetailKey, Lookup>,
+    detail_sender: Sender<(DetailKey, Result<Details, String>)>,
+    detail_receiver: Receiver<(DetailKey, Result<Details, String>)>,
+    /// The item under the cursor and since when, for `DETAIL_DELAY`.
+    detail_target: Option<(usize, Instant)>,
+    /// Search typed after `/` on the selection screen.
+    filter: String,
+    /// Whether keys are being typed into `filter`.
+    filtering: bool,
     mode: Mode,
+    diagnostics: Vec<Diagnostic>,
+    diagnostics_index: usize,
+    drift: Vec<DriftItem>,
+    drift_index: usize,
+    operation: Operation,
+    /// Installed software absent from the Brewfile, offered for removal.
+    removals: Vec<ToolState>,
+    /// Installed software with a newer version available.
+    upgrades: Vec<ToolState>,
+    /// Include auto-updating casks when looking for upgrades.
+    greedy_upgrades: bool,
+    /// Cursor in the removal or upgrade list.
+    operation_index: usize,
+    brewfile_contents: String,
+    brewfile_path: Option<PathBuf>,
+    input: String,
+    /// Existing file the user was warned about on the save screen; pressing
+    /// enter again on the same path overwrites it.
+    overwrite: Option<PathBuf>,
     info: Option<String>,
     progress: Option<String>,
+    /// Progress events from the worker pool while an operation runs.
+    jobs: Option<Receiver<JobEvent>>,
+    /// Cancels the running operation; cleared once it ends.
+    cancel: Option<Arc<CancelToken>>,
+    /// Whether the last operation was cancelled before it finished.
+    run_cancelled: bool,
+    jobs_total: usize,
+    jobs_completed: usize,
+    /// Cursor in the results list; its log is shown in the output pane.
+    results_index: usize,
+    /// Move the results cursor to each item as it starts.
+    follow_output: bool,
+    /// First visible line of the output pane, or `None` to stay at the end.
+    log_scroll: Option<usize>,
+    /// When each running item started, to time it.
+    started_at: HashMap<usize, Instant>,
+    /// When the current or last operation started, naming its history entry.
+    run_started: SystemTime,
+    history: Vec<RunSummary>,
+    history_index: usize,
+    /// Items of a past run reopened from the history view.
+    past_run: Option<Vec<ToolState>>,
+    /// First visible line of the full-screen log viewer.
+    log_view_scroll: usize,
+    /// Text searched for in the log viewer.
+    log_search: String,
+    /// Whether `/` is collecting a search in `input`.
+    log_searching: bool,
+    /// Incremented on every pass of the event loop; drives the spinner.
+    tick: usize,
     should_quit: bool,
     symbols: Symbols,
+    /// Whether any item of the last finished operation failed or was
+    /// cancelled; decides the exit status.
+    run_failed: bool,
+    options: cli::Options,
 }
 
 impl App {
-    fn new() -> Result<Self, String> {
-        let (items, info) = load_tools_from_brewfile()?;
-        Ok(Self {
-            items,
+    fn new(source: Option<&str>) -> Result<Self, String> {
+        let loaded = load_tools_from_brewfile(source)?;
+        let by_category = loaded.items.iter().any(|item| item.tool.category.is_some());
+        let (detail_sender, detail_receiver) = mpsc::channel();
+        let mut app = Self {
+            items: loaded.items,
             index: 0,
+            visible: Vec::new(),
+            rows: Vec::new(),
+            collapsed: HashSet::new(),
+            by_category,
+            installed: None,
+            probe: None,
+            listing: None,
+            show_details: true,
+            details: HashMap::new(),
+            detail_sender,
+            detail_receiver,
+            detail_target: None,
+            filter: String::new(),
+            filtering: false,
             mode: Mode::Selecting,
-            info,
+            diagnostics: loaded.diagnostics,
+            diagnostics_index: 0,
+            drift: Vec::new(),
+            drift_index: 0,
+            operation: Operation::Install,
+            removals: Vec::new(),
+            upgrades: Vec::new(),
+            greedy_upgrades: false,
+            operation_index: 0,
+            brewfile_contents: loaded.contents,
+            brewfile_path: loaded.path,
+            input: String::new(),
+            overwrite: None,
+            info: loaded.note,
             progress: None,
+            jobs: None,
+            cancel: None,
+            run_cancelled: false,
+            jobs_total: 0,
+            jobs_completed: 0,
+            results_index: 0,
+            follow_output: true,
+            log_scroll: None,
+            started_at: HashMap::new(),
+            run_started: SystemTime::now(),
+            history: Vec::new(),
+            history_index: 0,
+            past_run: None,
+            log_view_scroll: 0,
+            log_search: String::new(),
+            log_searching: false,
+            tick: 0,
             should_quit: false,
             symbols: Symbols::new(),
-        })
+            run_failed: false,
+            options: cli::Options::default(),
+        };
+        app.refresh_visible();
+        Ok(app)
     }
 
     fn handle_key(&mut self, key: KeyEvent) -> Action {
+        // Raw mode delivers Ctrl-C as a key press instead of a signal.
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            if self.jobs.is_some() {
+                self.cancel_operation();
+            } else {
+                self.should_quit = true;
+            }
+            return Action::None;
+        }
         match self.mode {
             Mode::Selecting => self.handle_selecting(key),
             Mode::Confirm => self.handle_confirm(key),
             Mode::Results => self.handle_results(key),
+            Mode::Log => self.handle_log(key),
+            Mode::History => self.handle_history(key),
+            Mode::Diagnostics => self.handle_diagnostics(key),
+            Mode::Save => self.handle_save(key),
+            Mode::AddEntry => self.handle_add_entry(key),
+            Mode::Export => self.handle_export(key),
+            Mode::Drift => self.handle_drift(key),
+            Mode::Cleanup | Mode::Upgrade => self.handle_operation_list(key),
         }
     }
 
     fn handle_selecting(&mut self, key: KeyEvent) -> Action {
+        if self.filtering {
+            match key.code {
+                KeyCode::Esc => {
+                    self.filtering = false;
+                    self.set_filter(String::new());
+                }
+                KeyCode::Enter => self.filtering = false,
+                KeyCode::Up => self.index = self.index.saturating_sub(1),
+                KeyCode::Down if self.index + 1 < self.rows.len() => self.index += 1,
+                KeyCode::Backspace => {
+                    let mut filter = self.filter.clone();
+                    filter.pop();
+                    self.set_filter(filter);
+                }
+                KeyCode::Char(ch) => {
+                    let filter = format!("{}{ch}", self.filter);
+                    self.set_filter(filter);
+                }
+                _ => {}
+            }
+            return Action::None;
+        }
         match key.code {
+            KeyCode::Esc if !self.filter.is_empty() => {
+                self.set_filter(String::new());
+                self.info = None;
+            }
             KeyCode::Char('q') | KeyCode::Esc => {
                 self.should_quit = true;
             }
+            KeyCode::Char('/') => {
+                self.filtering = true;
+                self.info = None;
+            }
             KeyCode::Up | KeyCode::Char('k') => {
                 self.info = None;
                 if self.index > 0 {
@@ -198,29 +694,146 @@ impl App {
             }
             KeyCode::Down | KeyCode::Char('j') => {
                 self.info = None;
-                if self.index + 1 < self.items.len() {
+                if self.index + 1 < self.rows.len() {
                     self.index += 1;
                 }
             }
-            KeyCode::Char(' ') => {
-                if let Some(item) = self.items.get_mut(self.index) {
-                    item.selected = !item.selected;
+            KeyCode::Tab | KeyCode::Char(']') => {
+                self.info = None;
+                self.jump_section(true);
+            }
+            KeyCode::BackTab | KeyCode::Char('[') => {
+                self.info = None;
+                self.jump_section(false);
+            }
+            KeyCode::Left | KeyCode::Right | KeyCode::Char('c') | KeyCode::Char('C') => {
+                if let Some(section) = self.current_section() {
+                    let collapse = match key.code {
+                        KeyCode::Left => true,
+                        KeyCode::Right => false,
+                        _ => !self.collapsed.contains(&section),
+                    };
+                    self.set_collapsed(section, collapse);
                     self.info = None;
                 }
             }
+            KeyCode::Char('t') | KeyCode::Char('T') => {
+                if let Some(section) = self.current_section() {
+                    self.toggle_section(section);
+                }
+            }
+            KeyCode::Char(' ') if matches!(self.rows.get(self.index), Some(Row::Section(_))) => {
+                if let Some(section) = self.current_section() {
+                    self.toggle_section(section);
+                }
+            }
+            KeyCode::Char('m') | KeyCode::Char('M') => self.select_missing(),
+            KeyCode::Char('i') | KeyCode::Char('I') => {
+                self.show_details = !self.show_details;
+                self.info = None;
+            }
+            KeyCode::Char('g') | KeyCode::Char('G') => {
+                let current = self.current_item();
+                self.by_category = !self.by_category;
+                self.refresh_visible();
+                if let Some(idx) = current {
+                    self.index = self
+                        .rows
+                        .iter()
+                        .position(|row| *row == Row::Item(idx))
+                        .unwrap_or(0);
+                }
+                self.info = Some(if self.by_category {
+                    "Grouped by Brewfile section.".to_string()
+                } else {
+                    "Grouped by kind.".to_string()
+                });
+            }
+            KeyCode::Char(' ') => {
+                if let Some(item) = self.current_item().and_then(|idx| self.items.get_mut(idx)) {
+                    if let Some(reason) = &item.excluded {
+                        self.info = Some(format!(
+                            "{} does not apply to this machine: {reason}",
+                            item.tool.name
+                        ));
+                    } else {
+                        item.selected = !item.selected;
+                        self.info = None;
+                    }
+                }
+            }
             KeyCode::Char('a') | KeyCode::Char('A') => {
-                for item in &mut self.items {
-                    item.selected = true;
+                for &idx in &self.visible {
+                    let item = &mut self.items[idx];
+                    item.selected = item.excluded.is_none();
                 }
-                self.info = Some("All tools selected.".to_string());
+                self.info = Some(if self.filter.is_empty() {
+                    "All tools selected.".to_string()
+                } else {
+                    format!("Selected the {} matching tools.", self.visible.len())
+                });
             }
             KeyCode::Char('d') | KeyCode::Char('D') => {
-                for item in &mut self.items {
-                    item.selected = false;
+                for &idx in &self.visible {
+                    self.items[idx].selected = false;
+                }
+                self.info = Some(if self.filter.is_empty() {
+                    "Selections cleared.".to_string()
+                } else {
+                    format!("Deselected the {} matching tools.", self.visible.len())
+                });
+            }
+            KeyCode::Char('s') | KeyCode::Char('S') => {
+                self.input = self.default_save_path().display().to_string();
+                self.overwrite = None;
+                self.mode = Mode::Save;
+                self.info = None;
+            }
+            KeyCode::Char('e') | KeyCode::Char('E') => {
+                self.input = DEFAULT_EXPORT_PATH.to_string();
+                self.mode = Mode::Export;
+                self.info = None;
+            }
+            KeyCode::Char('r') | KeyCode::Char('R') => {
+                self.info = None;
+                return Action::Drift;
+            }
+            KeyCode::Char('x') | KeyCode::Char('X') => {
+                self.info = None;
+                return Action::Cleanup;
+            }
+            KeyCode::Char('u') | KeyCode::Char('U') => {
+                self.info = None;
+                return Action::Upgrade;
+            }
+            KeyCode::Char('h') | KeyCode::Char('H') => {
+                self.history = history::list();
+                self.history_index = 0;
+                if self.history.is_empty() {
+                    self.info = Some(match history::state_dir() {
+                        Some(dir) => format!("No past runs in {}.", dir.display()),
+                        None => "No past runs.".to_string(),
+                    });
+                } else {
+                    self.mode = Mode::History;
+                    self.info = None;
+                }
+            }
+            KeyCode::Char('+') => {
+                self.input.clear();
+                self.mode = Mode::AddEntry;
+                self.info = None;
+            }
+            KeyCode::Char('w') | KeyCode::Char('W') => {
+                if self.diagnostics.is_empty() {
+                    self.info = Some("The Brewfile parsed without warnings.".to_string());
+                } else {
+                    self.mode = Mode::Diagnostics;
+                    self.info = None;
                 }
-                self.info = Some("Selections cleared.".to_string());
             }
             KeyCode::Enter => {
+                self.operation = Operation::Install;
                 if self.selected_count() == 0 {
                     self.info = Some("Select at least one tool before continuing.".to_string());
                 } else {
@@ -241,7 +854,11 @@ impl App {
                 Action::StartInstall
             }
             KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
-                self.mode = Mode::Selecting;
+                self.mode = match self.operation {
+                    Operation::Install => Mode::Selecting,
+                    Operation::Uninstall => Mode::Cleanup,
+                    Operation::Upgrade => Mode::Upgrade,
+                };
                 self.progress = None;
                 Action::None
             }
@@ -255,307 +872,2337 @@ impl App {
 
     fn handle_results(&mut self, key: KeyEvent) -> Action {
         match key.code {
-            KeyCode::Enter | KeyCode::Char('q') | KeyCode::Esc => {
+            KeyCode::Char('q') | KeyCode::Esc if self.past_run.is_some() => {
+                self.past_run = None;
+                self.mode = Mode::History;
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                // Kill running commands so they do not outlive the TUI.
+                if let Some(cancel) = &self.cancel {
+                    cancel.cancel();
+                }
                 self.should_quit = true;
             }
+            KeyCode::Char('c') | KeyCode::Char('C') if self.jobs.is_some() => {
+                self.cancel_operation();
+            }
+            KeyCode::Enter => {
+                let has_status = self
+                    .targets()
+                    .get(self.results_index)
+                    .is_some_and(|item| item.status.is_some());
+                if has_status {
+                    self.log_view_scroll = 0;
+                    self.log_searching = false;
+                    self.mode = Mode::Log;
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.results_index = self.results_index.saturating_sub(1);
+                self.follow_output = false;
+                self.log_scroll = None;
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.results_index + 1 < self.targets().len() {
+                    self.results_index += 1;
+                }
+                self.follow_output = false;
+                self.log_scroll = None;
+            }
+            KeyCode::PageUp => {
+                let top = self.log_scroll.unwrap_or_else(|| self.current_log_len());
+                self.log_scroll = Some(top.saturating_sub(LOG_PAGE));
+            }
+            KeyCode::PageDown => {
+                if let Some(top) = self.log_scroll {
+                    let top = top + LOG_PAGE;
+                    self.log_scroll = (top < self.current_log_len()).then_some(top);
+                }
+            }
+            KeyCode::Char('f') | KeyCode::Char('F') => {
+                self.follow_output = true;
+                self.log_scroll = None;
+            }
             _ => {}
         }
         Action::None
     }
 
-    fn selected_count(&self) -> usize {
-        self.items.iter().filter(|item| item.selected).count()
-    }
-
-    fn selected_indices(&self) -> Vec<usize> {
-        self.items
-            .iter()
-            .enumerate()
-            .filter_map(|(idx, item)| if item.selected { Some(idx) } else { None })
-            .collect()
-    }
-
-    fn clear_statuses(&mut self) {
-        for item in &mut self.items {
-            item.status = None;
+    fn handle_diagnostics(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Char('q')
+            | KeyCode::Char('w')
+            | KeyCode::Char('W')
+            | KeyCode::Esc
+            | KeyCode::Enter => {
+                self.mode = Mode::Selecting;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.diagnostics_index = self.diagnostics_index.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j')
+                if self.diagnostics_index + 1 < self.diagnostics.len() =>
+            {
+                self.diagnostics_index += 1;
+            }
+            _ => {}
         }
+        Action::None
     }
 
-    fn set_status(&mut self, idx: usize, status: Status) {
-        if let Some(item) = self.items.get_mut(idx) {
-            item.status = Some(status);
+    fn handle_save(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Selecting;
+            }
+            KeyCode::Enter => {
+                let path = expand_home(self.input.trim());
+                if path.exists() && self.overwrite.as_ref() != Some(&path) {
+                    self.info = Some(format!(
+                        "{} already exists. Press enter again to overwrite it.",
+                        path.display()
+                    ));
+                    self.overwrite = Some(path);
+                    return Action::None;
+                }
+                match self.save_selection(&path) {
+                    Ok(count) => {
+                        self.info = Some(format!(
+                            "Wrote {count} entr{} to {}",
+                            if count == 1 { "y" } else { "ies" },
+                            path.display()
+                        ));
+                        self.mode = Mode::Selecting;
+                    }
+                    Err(err) => self.info = Some(err),
+                }
+            }
+            _ => {
+                edit_input(&mut self.input, key);
+                self.overwrite = None;
+            }
         }
+        Action::None
     }
 
-    fn current_description(&self) -> Option<&str> {
-        self.items
-            .get(self.index)
-            .map(|item| item.tool.description.as_str())
-    }
-
-    fn selected_labels(&self) -> Vec<String> {
-        self.items
-            .iter()
-            .filter(|item| item.selected)
-            .map(|item| item.tool.label.clone())
-            .collect()
-    }
-}
-
-fn load_tools_from_brewfile() -> Result<(Vec<ToolState>, Option<String>), String> {
-    let (contents, note) = load_brewfile_text()?;
-    let items = parse_brewfile(&contents);
-    if items.is_empty() {
-        Err("Brewfile did not contain any brew/cask/mas entries".to_string())
-    } else {
-        Ok((items, note))
+    fn handle_add_entry(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Selecting;
+            }
+            KeyCode::Enter => match self.add_entry(self.input.trim().to_string()) {
+                Ok(label) => {
+                    self.info = Some(format!("Added {label}. Press s to save it to a Brewfile."));
+                    self.mode = Mode::Selecting;
+                }
+                Err(err) => self.info = Some(err),
+            },
+            _ => edit_input(&mut self.input, key),
+        }
+        Action::None
     }
-}
 
-fn load_brewfile_text() -> Result<(String, Option<String>), String> {
-    if let Ok(source) = std::env::var(BREWFILE_SOURCE_ENV) {
-        let trimmed = source.trim();
-        if trimmed.is_empty() {
-            return Err(format!("{BREWFILE_SOURCE_ENV} was set but empty"));
+    fn handle_export(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Selecting;
+                Action::None
+            }
+            KeyCode::Enter if self.input.trim().is_empty() => {
+                self.info = Some("Enter a path to write the Brewfile to.".to_string());
+                Action::None
+            }
+            KeyCode::Enter => Action::Export,
+            _ => {
+                edit_input(&mut self.input, key);
+                Action::None
+            }
         }
-        let text = load_spec(trimmed)?;
-        return Ok((text, Some(format!("Loaded Brewfile from {trimmed}"))));
     }
 
-    if let Ok(path) = std::env::var(BREWFILE_PATH_ENV) {
-        let trimmed = path.trim();
-        if trimmed.is_empty() {
-            return Err(format!("{BREWFILE_PATH_ENV} was set but empty"));
+    fn handle_drift(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.mode = Mode::Selecting;
+                self.info = None;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.drift_index = self.drift_index.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') if self.drift_index + 1 < self.drift.len() => {
+                self.drift_index += 1;
+            }
+            KeyCode::Char(' ') => {
+                if let Some(item) = self.drift.get_mut(self.drift_index) {
+                    if item.state == DriftState::Installed {
+                        self.info = Some(format!("{} is already installed.", item.label));
+                    } else {
+                        item.selected = !item.selected;
+                        self.info = None;
+                    }
+                }
+            }
+            KeyCode::Char('i') | KeyCode::Char('I') => {
+                let missing = self.drift_targets(DriftState::Missing);
+                if missing.is_empty() {
+                    self.info = Some("Nothing from the Brewfile is missing.".to_string());
+                } else {
+                    let indices: HashSet<usize> = missing
+                        .iter()
+                        .filter_map(|&idx| self.drift[idx].item)
+                        .collect();
+                    for (idx, item) in self.items.iter_mut().enumerate() {
+                        item.selected = indices.contains(&idx);
+                    }
+                    self.operation = Operation::Install;
+                    self.mode = Mode::Confirm;
+                    self.info = None;
+                }
+            }
+            KeyCode::Char('a') | KeyCode::Char('A') => {
+                let extras = self.drift_targets(DriftState::Extra);
+                self.info = Some(if extras.is_empty() {
+                    "Nothing is installed outside the Brewfile.".to_string()
+                } else {
+                    match self.append_extras(&extras) {
+                        Ok((count, path)) => format!(
+                            "Appended {count} entr{} to {}",
+                            if count == 1 { "y" } else { "ies" },
+                            path.display()
+                        ),
+                        Err(err) => err,
+                    }
+                });
+            }
+            _ => {}
         }
-        let text = load_from_path(trimmed)?;
-        return Ok((text, Some(format!("Loaded Brewfile from {trimmed}"))));
+        Action::None
     }
 
-    if let Ok(url) = std::env::var(BREWFILE_URL_ENV) {
-        let trimmed = url.trim();
-        if trimmed.is_empty() {
-            return Err(format!("{BREWFILE_URL_ENV} was set but empty"));
+    /// Keys for the removal and upgrade lists, which `operation` selects.
+    fn handle_operation_list(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.mode = Mode::Selecting;
+                self.info = None;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.operation_index = self.operation_index.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j')
+                if self.operation_index + 1 < self.targets().len() =>
+            {
+                self.operation_index += 1;
+            }
+            KeyCode::Char(' ') => {
+                let index = self.operation_index;
+                if let Some(item) = self.targets_mut().get_mut(index) {
+                    item.selected = !item.selected;
+                }
+            }
+            KeyCode::Char('a') | KeyCode::Char('A') => {
+                for item in self.targets_mut() {
+                    item.selected = true;
+                }
+            }
+            KeyCode::Char('d') | KeyCode::Char('D') => {
+                for item in self.targets_mut() {
+                    item.selected = false;
+                }
+            }
+            KeyCode::Char('g') | KeyCode::Char('G') if self.mode == Mode::Upgrade => {
+                self.greedy_upgrades = !self.greedy_upgrades;
+                return Action::Upgrade;
+            }
+            KeyCode::Enter => {
+                if self.selected_count() == 0 {
+                    self.info = Some(format!(
+                        "Select at least one item to {}.",
+                        self.operation.verb().to_lowercase()
+                    ));
+                } else {
+                    self.mode = Mode::Confirm;
+                    self.info = None;
+                }
+            }
+            _ => {}
         }
-        let text = fetch_brewfile(trimmed)?;
-        return Ok((text, Some(format!("Loaded Brewfile from {trimmed}"))));
+        Action::None
     }
 
-    if let Some(path) = find_local_brewfile()? {
-        let display = path.display().to_string();
-        let text = fs::read_to_string(&path)
-            .map_err(|err| format!("Failed to read Brewfile at {display}: {err}"))?;
-        return Ok((text, Some(format!("Loaded Brewfile from {display}"))));
+    /// Indices into `drift` of the selected items in `state`, or of every
+    /// item in `state` when none of them are selected.
+    fn drift_targets(&self, state: DriftState) -> Vec<usize> {
+        let matching: Vec<usize> = self
+            .drift
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.state == state)
+            .map(|(idx, _)| idx)
+            .collect();
+        let selected: Vec<usize> = matching
+            .iter()
+            .copied()
+            .filter(|&idx| self.drift[idx].selected)
+            .collect();
+        if selected.is_empty() {
+            matching
+        } else {
+            selected
+        }
     }
 
-    let text = fetch_brewfile(DEFAULT_BREWFILE_URL)?;
-    Ok((
-        text,
-        Some(format!(
-            "Loaded Brewfile from {DEFAULT_BREWFILE_URL}. Override with {BREWFILE_SOURCE_ENV}, {BREWFILE_PATH_ENV}, or {BREWFILE_URL_ENV}."
-        )),
-    ))
-}
+    /// Appends the given extra items to the loaded Brewfile in place, next
+    /// to entries of the same kind, and reloads the list from the result.
+    fn append_extras(&mut self, extras: &[usize]) -> Result<(usize, PathBuf), String> {
+        let Some(path) = self.brewfile_path.clone() else {
+            return Err(
+                "The Brewfile was loaded from a URL; press s to save a local copy first."
+                    .to_string(),
+            );
+        };
+        let names = |kind: EntryKind| -> Vec<String> {
+            extras
+                .iter()
+                .map(|&idx| &self.drift[idx])
+                .filter(|item| item.kind == kind)
+                .map(|item| item.name.clone())
+                .collect()
+        };
+        let mut descriptions = inventory::brew_descriptions("--formula", &names(EntryKind::Brew));
+        descriptions.extend(inventory::brew_descriptions(
+            "--cask",
+            &names(EntryKind::Cask),
+        ));
 
-fn load_spec(spec: &str) -> Result<String, String> {
-    if looks_like_url(spec) {
-        fetch_brewfile(spec)
-    } else {
-        load_from_path(spec)
+        let parsed = brewfile::parse(&self.brewfile_contents, &Host::current());
+        let mut document = Document::new(&self.brewfile_contents, &parsed.entries);
+        for &idx in extras {
+            let item = &self.drift[idx];
+            if let Some(line) = &item.entry_line {
+                let description = match item.kind {
+                    EntryKind::Brew | EntryKind::Cask => descriptions.get(&item.name),
+                    _ => None,
+                };
+                document.insert_entry(item.kind, line, description.map(String::as_str));
+            }
+        }
+        let contents = document.render();
+        fs::write(&path, &contents)
+            .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+
+        for &idx in extras {
+            self.drift[idx].state = DriftState::Installed;
+            self.drift[idx].selected = false;
+        }
+        self.reload_contents(contents);
+        Ok((extras.len(), path))
     }
-}
 
-fn load_from_path(spec: &str) -> Result<String, String> {
-    let path = expand_home(spec);
-    let display = path.display().to_string();
-    fs::read_to_string(&path)
-        .map_err(|err| format!("Failed to read Brewfile at {display}: {err}"))
-}
+    /// Re-parses the Brewfile after it was edited on disk, keeping
+    /// selections and entries added from the TUI.
+    fn reload_contents(&mut self, contents: String) {
+        let (mut items, diagnostics) = parse_brewfile(&contents);
+        let previous = std::mem::take(&mut self.items);
+        for item in &mut items {
+            item.selected = previous.iter().any(|old| {
+                old.selected && old.tool.kind == item.tool.kind && old.tool.name == item.tool.name
+            });
+        }
+        for old in previous {
+            let present = items
+                .iter()
+                .any(|item| item.tool.kind == old.tool.kind && item.tool.name == old.tool.name);
+            if matches!(old.origin, Origin::Added(_)) && !present {
+                items.push(old);
+            }
+        }
+        for drift in &mut self.drift {
+            drift.item = items.iter().position(|item| {
+                item.excluded.is_none()
+                    && item.tool.kind.entry_kind() == drift.kind
+                    && item.tool.name == drift.name
+            });
+        }
+        self.items = items;
+        self.refresh_visible();
+        self.diagnostics = diagnostics;
+        self.diagnostics_index = 0;
+        self.brewfile_contents = contents;
+    }
 
-fn fetch_brewfile(url: &str) -> Result<String, String> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(10))
-        .user_agent("install-tools-tui")
-        .build()
-        .map_err(|err| format!("Failed to build HTTP client: {err}"))?;
+    /// `Brewfile.selection` next to the loaded Brewfile, or in the current
+    /// directory when it came from a URL, so saving never edits the source.
+    fn default_save_path(&self) -> PathBuf {
+        self.brewfile_path
+            .as_deref()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new(""))
+            .join(DEFAULT_SAVE_NAME)
+    }
 
-    let response = client
-        .get(url)
-        .send()
-        .map_err(|err| format!("Failed to fetch Brewfile from {url}: {err}"))?;
+    /// Writes the loaded Brewfile with unselected entries removed and added
+    /// entries inserted. Returns the number of entries written.
+    fn save_selection(&self, path: &Path) -> Result<usize, String> {
+        if path.as_os_str().is_empty() {
+            return Err("Enter a path to write the Brewfile to.".to_string());
+        }
+        let parsed = brewfile::parse(&self.brewfile_contents, &Host::current());
+        let mut document = Document::new(&self.brewfile_contents, &parsed.entries);
+
+        // Entries that do not apply to this host are kept for the others,
+        // and so are the taps the kept `owner/repo/name` entries live in.
+        let kept = |item: &ToolState| item.selected || item.excluded.is_some();
+        let needed_taps: HashSet<String> = self
+            .items
+            .iter()
+            .filter(|item| kept(item))
+            .filter_map(|item| qualified_tap(&item.tool.name))
+            .map(|tap| tap.to_ascii_lowercase())
+            .collect();
+        let needed_tap = |item: &ToolState| {
+            item.tool.kind == ToolKind::Tap
+                && needed_taps.contains(&item.tool.name.to_ascii_lowercase())
+        };
+        let removed: HashSet<usize> = self
+            .items
+            .iter()
+            .filter(|item| !kept(item) && !needed_tap(item))
+            .filter_map(|item| match item.origin {
+                Origin::Line(line) => Some(line),
+                _ => None,
+            })
+            .collect();
+        document.remove_lines(&removed);
+
+        for item in self.items.iter().filter(|item| item.selected) {
+            if let Origin::Added(text) = &item.origin {
+                document.insert_entry(item.tool.kind.entry_kind(), text, None);
+            }
+        }
 
-    if !response.status().is_success() {
-        return Err(format!(
-            "Request to {url} returned status {}",
-            response.status()
-        ));
+        fs::write(path, document.render())
+            .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+        Ok(self
+            .items
+            .iter()
+            .filter(|item| match item.origin {
+                Origin::Line(line) => !removed.contains(&line),
+                Origin::Added(_) => item.selected,
+                Origin::Implied | Origin::Installed => false,
+            })
+            .count())
     }
 
-    response
-        .text()
-        .map_err(|err| format!("Failed to read Brewfile contents: {err}"))
-}
+    /// Parses a single Brewfile line typed into the TUI and adds it to the
+    /// list, selected. Returns the new item's label.
+    fn add_entry(&mut self, line: String) -> Result<String, String> {
+        if line.is_empty() {
+            return Err("Type a Brewfile line such as brew \"ripgrep\".".to_string());
+        }
+        let (tools, diagnostics) = parse_brewfile(&line);
+        if let Some(diag) = diagnostics.first() {
+            return Err(format!("Could not add entry: {}", diag.message));
+        }
+        let Some(mut state) = tools
+            .into_iter()
+            .find(|state| !matches!(state.origin, Origin::Implied))
+        else {
+            return Err(
+                "Could not add entry: no tap/brew/cask/mas/vscode/go entry found".to_string(),
+            );
+        };
+        if let Some(reason) = &state.excluded {
+            return Err(format!("Could not add entry: {reason}"));
+        }
+        let duplicate = self
+            .items
+            .iter()
+            .any(|item| item.tool.kind == state.tool.kind && item.tool.name == state.tool.name);
+        if duplicate {
+            return Err(format!("{} is already in the list", state.tool.label));
+        }
+        state.selected = true;
+        state.origin = Origin::Added(line);
+        let label = state.tool.label.clone();
+        self.items.push(state);
+        // Clear the search and open its section so the new entry is in view.
+        let tool = &self.items[self.items.len() - 1].tool;
+        let section = self.section_of(tool);
+        self.collapsed.remove(&section);
+        self.set_filter(String::new());
+        let row = Row::Item(self.items.len() - 1);
+        self.index = self.rows.iter().position(|r| *r == row).unwrap_or(0);
+        Ok(label)
+    }
 
-fn looks_like_url(spec: &str) -> bool {
-    let lower = spec.to_ascii_lowercase();
-    lower.starts_with("http://") || lower.starts_with("https://")
-}
+    fn ignored_line_count(&self) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diag| diag.severity == Severity::Ignored)
+            .count()
+    }
 
-fn expand_home(spec: &str) -> PathBuf {
-    if let Some(rest) = spec.strip_prefix("~/") {
-        if let Ok(home) = std::env::var("HOME") {
-            return Path::new(&home).join(rest);
+    fn handle_history(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.mode = Mode::Selecting;
+                self.info = None;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.history_index = self.history_index.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') if self.history_index + 1 < self.history.len() => {
+                self.history_index += 1;
+            }
+            KeyCode::Enter => {
+                let Some(run) = self.history.get(self.history_index).cloned() else {
+                    return Action::None;
+                };
+                match history::load(&run.dir) {
+                    Ok(items) => {
+                        self.past_run = Some(items);
+                        self.operation = run.operation;
+                        self.results_index = 0;
+                        self.follow_output = false;
+                        self.log_scroll = None;
+                        self.progress = Some(format!(
+                            "Run from {} ({}). Press Enter to view an item's log, esc to go back.",
+                            run.started,
+                            run.dir.display()
+                        ));
+                        self.info = None;
+                        self.mode = Mode::Results;
+                    }
+                    Err(err) => self.info = Some(err),
+                }
+            }
+            _ => {}
         }
-    } else if spec == "~" {
-        if let Ok(home) = std::env::var("HOME") {
-            return PathBuf::from(home);
+        Action::None
+    }
+
+    fn handle_log(&mut self, key: KeyEvent) -> Action {
+        if self.log_searching {
+            match key.code {
+                KeyCode::Esc => self.log_searching = false,
+                KeyCode::Enter => {
+                    self.log_searching = false;
+                    self.log_search = self.input.trim().to_string();
+                    self.jump_to_match(self.log_view_scroll, true);
+                }
+                _ => edit_input(&mut self.input, key),
+            }
+            return Action::None;
+        }
+
+        let last_line = self.current_log_len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc | KeyCode::Enter => {
+                self.mode = Mode::Results;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.log_view_scroll = self.log_view_scroll.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.log_view_scroll = (self.log_view_scroll + 1).min(last_line);
+            }
+            KeyCode::PageUp => {
+                self.log_view_scroll = self.log_view_scroll.saturating_sub(LOG_PAGE);
+            }
+            KeyCode::PageDown => {
+                self.log_view_scroll = (self.log_view_scroll + LOG_PAGE).min(last_line);
+            }
+            KeyCode::Home | KeyCode::Char('g') => self.log_view_scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => self.log_view_scroll = last_line,
+            KeyCode::Char('/') => {
+                self.input = self.log_search.clone();
+                self.log_searching = true;
+            }
+            KeyCode::Char('n') => self.jump_to_match(self.log_view_scroll + 1, true),
+            KeyCode::Char('N') => self.jump_to_match(self.log_view_scroll, false),
+            _ => {}
         }
+        Action::None
     }
-    PathBuf::from(spec)
-}
 
-fn find_local_brewfile() -> Result<Option<PathBuf>, String> {
-    let mut dir = std::env::current_dir()
-        .map_err(|err| format!("Failed to determine current directory: {err}"))?;
-    loop {
-        let candidate = dir.join("Brewfile");
-        if candidate.is_file() {
-            return Ok(Some(candidate));
+    /// Scrolls the log viewer to the next line matching the search at or
+    /// after `from`, or the previous one before it, wrapping around.
+    fn jump_to_match(&mut self, from: usize, forward: bool) {
+        let matches = self.log_matches();
+        if matches.is_empty() {
+            return;
         }
-        if !dir.pop() {
-            break;
+        let target = if forward {
+            matches
+                .iter()
+                .find(|&&line| line >= from)
+                .or(matches.first())
+        } else {
+            matches
+                .iter()
+                .rev()
+                .find(|&&line| line < from)
+                .or(matches.last())
+        };
+        if let Some(&line) = target {
+            self.log_view_scroll = line;
         }
     }
-    Ok(None)
-}
 
-fn parse_brewfile(contents: &str) -> Vec<ToolState> {
-    let mut tools = Vec::new();
-    let mut seen = HashSet::new();
-    let mut pending_comment: Option<String> = None;
+    /// Lines of the viewed log containing the search text, ignoring case.
+    fn log_matches(&self) -> Vec<usize> {
+        if self.log_search.is_empty() {
+            return Vec::new();
+        }
+        let needle = self.log_search.to_lowercase();
+        let Some(item) = self.targets().get(self.results_index) else {
+            return Vec::new();
+        };
+        log_entries(&item.log)
+            .iter()
+            .enumerate()
+            .filter(|(_, (text, _))| text.to_lowercase().contains(&needle))
+            .map(|(line, _)| line)
+            .collect()
+    }
 
-    for line in contents.lines() {
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            pending_comment = None;
-            continue;
+    /// Hands the selected items to the worker pool; `poll_jobs` picks up
+    /// their progress from the event loop.
+    fn start_operation(&mut self) {
+        self.clear_statuses();
+        let jobs: Vec<(usize, Tool)> = self
+            .selected_indices()
+            .into_iter()
+            .map(|idx| (idx, self.targets()[idx].tool.clone()))
+            .collect();
+        self.jobs_total = jobs.len();
+        self.jobs_completed = 0;
+        self.results_index = jobs.first().map_or(0, |(idx, _)| *idx);
+        self.started_at.clear();
+        self.run_started = SystemTime::now();
+        self.follow_output = true;
+        self.log_scroll = None;
+        let cancel = Arc::new(CancelToken::default());
+        self.run_cancelled = false;
+        self.jobs = Some(pool::spawn(
+            jobs,
+            self.operation,
+            pool::worker_count(),
+            Some(Arc::clone(&cancel)),
+        ));
+        self.cancel = Some(cancel);
+        self.update_progress();
+    }
+
+    /// Kills the running commands; the workers then report every item they
+    /// had not finished as cancelled.
+    fn cancel_operation(&mut self) {
+        if let Some(cancel) = &self.cancel {
+            cancel.cancel();
         }
+        self.update_progress();
+    }
 
-        if let Some(comment) = trimmed.strip_prefix('#') {
-            let text = comment.trim();
-            if text.is_empty() {
-                continue;
+    fn cancelling(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.is_cancelled())
+    }
+
+    /// Applies every progress event that has arrived since the last call,
+    /// without waiting for more.
+    fn poll_jobs(&mut self) {
+        let Some(receiver) = &self.jobs else {
+            return;
+        };
+        let mut events = Vec::new();
+        let finished = loop {
+            match receiver.try_recv() {
+                Ok(event) => events.push(event),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
             }
-            match &mut pending_comment {
-                Some(existing) => {
-                    existing.push(' ');
-                    existing.push_str(text);
-                }
-                None => pending_comment = Some(text.to_string()),
-            }
-            continue;
-        }
-
-        if let Some(name) = extract_first_quoted(trimmed) {
-            if trimmed.starts_with("brew ") {
-                let key = (ToolKind::BrewFormula, name.clone());
-                if seen.insert(key) {
-                    let description = pending_comment
-                        .take()
-                        .unwrap_or_else(|| format!("Homebrew formula '{name}'"));
-                    tools.push(ToolState {
-                        tool: Tool {
-                            kind: ToolKind::BrewFormula,
-                            name: name.clone(),
-                            label: format!("{name} (brew formula)"),
-                            description,
-                            fallbacks: Vec::new(),
-                            app_id: None,
-                        },
-                        selected: false,
-                        status: None,
-                    });
-                } else {
-                    pending_comment = None;
+        };
+        let verb = self.operation.progressive();
+        for event in events {
+            match event {
+                JobEvent::Started(idx) => {
+                    self.started_at.insert(idx, Instant::now());
+                    self.set_status(idx, Status::Pending(format!("{verb}...")));
+                    if self.follow_output {
+                        self.results_index = idx;
+                        self.log_scroll = None;
+                    }
+                }
+                JobEvent::Log(idx, event) => {
+                    if let Some(item) = self.targets_mut().get_mut(idx) {
+                        item.log.push(event);
+                    }
+                }
+                JobEvent::Finished(idx, status) => {
+                    self.jobs_completed += 1;
+                    self.set_status(idx, status);
+                    let duration = self.started_at.remove(&idx).map(|start| start.elapsed());
+                    if let Some(item) = self.targets_mut().get_mut(idx) {
+                        item.duration = duration;
+                    }
                 }
-                continue;
             }
+        }
+        if finished {
+            self.jobs = None;
+            self.run_cancelled = self.cancelling();
+            self.cancel = None;
+        }
+        self.update_progress();
+        if finished {
+            self.record_run();
+        }
+    }
 
-            if trimmed.starts_with("cask ") {
-                let key = (ToolKind::BrewCask, name.clone());
-                if seen.insert(key) {
-                    let description = pending_comment
-                        .take()
-                        .unwrap_or_else(|| format!("Homebrew cask '{name}'"));
-                    tools.push(ToolState {
-                        tool: Tool {
-                            kind: ToolKind::BrewCask,
-                            name: name.clone(),
-                            label: format!("{name} (cask)"),
-                            description,
-                            fallbacks: Vec::new(),
-                            app_id: None,
-                        },
-                        selected: false,
-                        status: None,
-                    });
-                } else {
-                    pending_comment = None;
-                }
-                continue;
+    /// Saves the finished run to the history directory, writes any
+    /// requested reports, and notes where they went.
+    fn record_run(&mut self) {
+        let mut notes = vec![
+            match history::save(self.operation, self.run_started, self.targets()) {
+                Ok(dir) => format!("Saved to {}.", dir.display()),
+                Err(err) => format!("Run history not saved: {err}"),
+            },
+        ];
+        notes.extend(report::write_requested(
+            &self.options,
+            self.operation,
+            self.run_started,
+            self.targets(),
+        ));
+        self.run_failed = report::any_failed(self.targets());
+        // What is installed has changed.
+        self.start_probe();
+        self.details.clear();
+        if let Some(progress) = &mut self.progress {
+            for note in notes {
+                progress.push(' ');
+                progress.push_str(&note);
+            }
+        }
+    }
+
+    fn update_progress(&mut self) {
+        let (completed, total) = (self.jobs_completed, self.jobs_total);
+        if self.jobs.is_none() {
+            let finished = match self.operation {
+                Operation::Install => "Installation",
+                Operation::Uninstall => "Removal",
+                Operation::Upgrade => "Upgrade",
+            };
+            let state = if self.run_cancelled {
+                "cancelled"
+            } else {
+                "complete"
+            };
+            self.progress = Some(format!(
+                "{finished} {state}. Press Enter to view an item's log, q to exit."
+            ));
+            return;
+        }
+        let running: Vec<&str> = self
+            .targets()
+            .iter()
+            .filter(|item| matches!(item.status, Some(Status::Pending(_))))
+            .map(|item| item.tool.label.as_str())
+            .collect();
+        self.progress = Some(if self.cancelling() {
+            format!("Cancelling... completed {completed}/{total}")
+        } else if running.is_empty() {
+            format!("Completed {completed}/{total}")
+        } else {
+            format!(
+                "Completed {completed}/{total}; {} {} [c - Cancel] [q - Quit]",
+                self.operation.progressive(),
+                running.join(", ")
+            )
+        });
+    }
+
+    fn current_log_len(&self) -> usize {
+        self.targets()
+            .get(self.results_index)
+            .map_or(0, |item| log_lines(&item.log).len())
+    }
+
+    /// The list the current operation works on.
+    fn targets(&self) -> &[ToolState] {
+        if let Some(items) = &self.past_run {
+            return items;
+        }
+        match self.operation {
+            Operation::Install => &self.items,
+            Operation::Uninstall => &self.removals,
+            Operation::Upgrade => &self.upgrades,
+        }
+    }
+
+    fn targets_mut(&mut self) -> &mut [ToolState] {
+        if let Some(items) = &mut self.past_run {
+            return items;
+        }
+        match self.operation {
+            Operation::Install => &mut self.items,
+            Operation::Uninstall => &mut self.removals,
+            Operation::Upgrade => &mut self.upgrades,
+        }
+    }
+
+    fn selected_count(&self) -> usize {
+        self.targets().iter().filter(|item| item.selected).count()
+    }
+
+    fn selected_indices(&self) -> Vec<usize> {
+        self.targets()
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, item)| if item.selected { Some(idx) } else { None })
+            .collect()
+    }
+
+    fn clear_statuses(&mut self) {
+        for item in self.targets_mut() {
+            item.status = None;
+            item.log.clear();
+            item.duration = None;
+        }
+    }
+
+    fn set_status(&mut self, idx: usize, status: Status) {
+        if let Some(item) = self.targets_mut().get_mut(idx) {
+            item.status = Some(status);
+        }
+    }
+
+    /// Starts listing installed software for `action` on a background
+    /// thread, replacing any listing still in progress.
+    fn start_listing(&mut self, action: Action) {
+        let listing = match action {
+            Action::Export => {
+                let path = expand_home(self.input.trim());
+                self.mode = Mode::Selecting;
+                Listing::Inventory(InventoryUse::Export(path), inventory::spawn())
             }
+            Action::Drift => Listing::Inventory(InventoryUse::Drift, inventory::spawn()),
+            Action::Cleanup => Listing::Inventory(InventoryUse::Cleanup, inventory::spawn()),
+            Action::Upgrade => Listing::Outdated(outdated::spawn(self.greedy_upgrades)),
+            Action::None | Action::StartInstall => return,
+        };
+        self.info = Some(match listing {
+            Listing::Inventory(..) => "Inspecting installed software...".to_string(),
+            Listing::Outdated(_) => "Checking for outdated software...".to_string(),
+        });
+        self.listing = Some(listing);
+    }
 
-            if trimmed.starts_with("mas ") {
-                if let Some(raw_id) = trimmed.split("id:").nth(1) {
-                    let app_id: String = raw_id.chars().filter(|ch| ch.is_ascii_digit()).collect();
-                    if !app_id.is_empty() {
-                        let key = (ToolKind::Mas, app_id.clone());
-                        if seen.insert(key) {
-                            let label = name.clone();
-                            let description = pending_comment.take().unwrap_or_else(|| {
-                                format!("Mac App Store app '{label}' (id {app_id})")
-                            });
-                            tools.push(ToolState {
-                                tool: Tool {
-                                    kind: ToolKind::Mas,
-                                    name: label.clone(),
-                                    label: format!("{label} (App Store)"),
-                                    description,
-                                    fallbacks: Vec::new(),
-                                    app_id: Some(app_id.clone()),
-                                },
-                                selected: false,
-                                status: None,
-                            });
-                        }
-                    }
+    /// Shows the listing once the background thread has delivered it.
+    fn poll_listing(&mut self) {
+        let Some(listing) = self.listing.take() else {
+            return;
+        };
+        let lost = "Listing installed software failed unexpectedly.".to_string();
+        match listing {
+            Listing::Inventory(usage, receiver) => match receiver.try_recv() {
+                Ok(inventory) => match usage {
+                    InventoryUse::Export(path) => self.export_inventory(&path, &inventory),
+                    InventoryUse::Drift => self.show_drift(&inventory),
+                    InventoryUse::Cleanup => self.show_removable(&inventory),
+                },
+                Err(TryRecvError::Empty) => {
+                    self.listing = Some(Listing::Inventory(usage, receiver));
                 }
-                pending_comment = None;
-                continue;
-            }
+                Err(TryRecvError::Disconnected) => self.info = Some(lost),
+            },
+            Listing::Outdated(receiver) => match receiver.try_recv() {
+                Ok(outdated) => self.show_outdated(outdated),
+                Err(TryRecvError::Empty) => self.listing = Some(Listing::Outdated(receiver)),
+                Err(TryRecvError::Disconnected) => self.info = Some(lost),
+            },
         }
+    }
 
-        pending_comment = None;
+    fn export_inventory(&mut self, path: &Path, inventory: &Inventory) {
+        let contents = inventory.to_brewfile();
+        self.info = Some(match fs::write(path, contents) {
+            Ok(()) => {
+                let mut message = format!(
+                    "Exported {} entries to {}.",
+                    inventory.entry_count(),
+                    path.display()
+                );
+                if !inventory.warnings.is_empty() {
+                    message.push_str(&format!(" {}", inventory.warnings.join("; ")));
+                }
+                message
+            }
+            Err(err) => format!("Failed to write {}: {err}", path.display()),
+        });
     }
 
-    tools
-}
+    fn show_drift(&mut self, inventory: &Inventory) {
+        self.drift = drift::compute(&self.items, inventory);
+        self.drift_index = 0;
+        self.info = (!inventory.warnings.is_empty()).then(|| inventory.warnings.join("; "));
+        self.mode = Mode::Drift;
+    }
 
-fn extract_first_quoted(line: &str) -> Option<String> {
-    let start = line.find('"')? + 1;
-    let rest = &line[start..];
-    let end = rest.find('"')?;
-    Some(rest[..end].to_string())
-}
+    fn show_removable(&mut self, inventory: &Inventory) {
+        self.removals = drift::cleanup_candidates(&self.items, inventory);
+        self.operation = Operation::Uninstall;
+        self.operation_index = 0;
+        self.info = (!inventory.warnings.is_empty()).then(|| inventory.warnings.join("; "));
+        if self.removals.is_empty() && self.info.is_none() {
+            self.info = Some("Everything installed is listed in the Brewfile.".to_string());
+        } else {
+            self.mode = Mode::Cleanup;
+        }
+    }
 
-fn draw(frame: &mut Frame, app: &App) {
-    match app.mode {
-        Mode::Selecting => draw_selection(frame, app),
-        Mode::Confirm => draw_confirm(frame, app),
-        Mode::Results => draw_results(frame, app),
+    fn show_outdated(&mut self, outdated: Outdated) {
+        self.upgrades = outdated
+            .items
+            .into_iter()
+            .map(|item| upgrade_tool_state(item, self.greedy_upgrades))
+            .collect();
+        self.operation = Operation::Upgrade;
+        self.operation_index = 0;
+        let mut notes = outdated.warnings;
+        if !outdated.pinned.is_empty() {
+            notes.push(format!(
+                "Pinned, not upgraded: {}",
+                outdated.pinned.join(", ")
+            ));
+        }
+        self.info = (!notes.is_empty()).then(|| notes.join("; "));
+        if self.upgrades.is_empty() && self.info.is_none() {
+            self.info = Some("Everything is up to date.".to_string());
+            self.mode = Mode::Selecting;
+        } else {
+            self.mode = Mode::Upgrade;
+        }
     }
+
+    fn start_probe(&mut self) {
+        self.probe = Some(installed::spawn());
+    }
+
+    fn poll_probe(&mut self) {
+        let Some(receiver) = &self.probe else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(installed) => {
+                self.installed = Some(installed);
+                self.probe = None;
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => self.probe = None,
+        }
+    }
+
+    /// Stores finished lookups, and starts one for the highlighted entry
+    /// once the cursor has rested on it for `DETAIL_DELAY`.
+    fn poll_details(&mut self) {
+        while let Ok((key, result)) = self.detail_receiver.try_recv() {
+            let lookup = match result {
+                Ok(details) => Lookup::Ready(details),
+                Err(err) => Lookup::Failed(err),
+            };
+            self.details.insert(key, lookup);
+        }
+        if !self.show_details || self.mode != Mode::Selecting {
+            return;
+        }
+        let Some(idx) = self.current_item() else {
+            self.detail_target = None;
+            return;
+        };
+        match self.detail_target {
+            Some((target, since)) if target == idx => {
+                let tool = &self.items[idx].tool;
+                let key = detail_key(tool);
+                if since.elapsed() >= DETAIL_DELAY && !self.details.contains_key(&key) {
+                    details::spawn(key.clone(), tool.clone(), self.detail_sender.clone());
+                    self.details.insert(key, Lookup::Loading);
+                }
+            }
+            _ => self.detail_target = Some((idx, Instant::now())),
+        }
+    }
+
+    /// Installed version of an item: `None` when it is missing or not yet
+    /// probed, and empty when the listing has no version.
+    fn installed_version(&self, tool: &Tool) -> Option<String> {
+        self.installed.as_ref()?.version(tool)
+    }
+
+    /// Selects the matching items that are not installed and clears the
+    /// ones that are.
+    fn select_missing(&mut self) {
+        if self.installed.is_none() {
+            self.info = Some("Still checking what is installed...".to_string());
+            return;
+        }
+        let mut missing = 0;
+        for &idx in &self.visible {
+            let installed = self.installed_version(&self.items[idx].tool).is_some();
+            let item = &mut self.items[idx];
+            item.selected = item.excluded.is_none() && !installed;
+            missing += usize::from(item.selected);
+        }
+        self.info = Some(match missing {
+            0 => "Everything listed is already installed.".to_string(),
+            _ => format!("Selected {missing} missing tool(s)."),
+        });
+    }
+
+    /// Index into `items` of the entry under the cursor.
+    fn current_item(&self) -> Option<usize> {
+        match self.rows.get(self.index)? {
+            Row::Item(idx) => Some(*idx),
+            Row::Section(_) => None,
+        }
+    }
+
+    /// The section the cursor is in, on its header or an item.
+    fn current_section(&self) -> Option<Section> {
+        match self.rows.get(self.index)? {
+            Row::Section(section) => Some(section.clone()),
+            Row::Item(idx) => Some(self.section_of(&self.items[*idx].tool)),
+        }
+    }
+
+    fn section_of(&self, tool: &Tool) -> Section {
+        if self.by_category {
+            Section::Category(tool.category.clone())
+        } else {
+            Section::Kind(tool.kind.clone())
+        }
+    }
+
+    /// Sections in display order: kinds in a fixed order, or categories in
+    /// the order the Brewfile introduces them with uncategorized entries
+    /// last.
+    fn sections(&self) -> Vec<Section> {
+        if !self.by_category {
+            return ToolKind::ALL.into_iter().map(Section::Kind).collect();
+        }
+        let mut sections = Vec::new();
+        for item in &self.items {
+            let section = Section::Category(item.tool.category.clone());
+            if item.tool.category.is_some() && !sections.contains(&section) {
+                sections.push(section);
+            }
+        }
+        sections.push(Section::Category(None));
+        sections
+    }
+
+    /// Items of a section that match the search, collapsed or not.
+    fn section_items(&self, section: &Section) -> Vec<usize> {
+        self.visible
+            .iter()
+            .copied()
+            .filter(|&idx| section.contains(&self.items[idx].tool))
+            .collect()
+    }
+
+    /// Selects every matching item of a section, or clears them all when
+    /// they already are.
+    fn toggle_section(&mut self, section: Section) {
+        let members: Vec<usize> = self
+            .section_items(&section)
+            .into_iter()
+            .filter(|&idx| self.items[idx].excluded.is_none())
+            .collect();
+        let select = members.iter().any(|&idx| !self.items[idx].selected);
+        for &idx in &members {
+            self.items[idx].selected = select;
+        }
+        let verb = if select { "Selected" } else { "Deselected" };
+        self.info = Some(format!("{verb} {} in {}.", members.len(), section.title()));
+    }
+
+    /// Folds or unfolds a section, leaving the cursor on its header.
+    fn set_collapsed(&mut self, section: Section, collapse: bool) {
+        if collapse {
+            self.collapsed.insert(section.clone());
+        } else {
+            self.collapsed.remove(&section);
+        }
+        self.refresh_visible();
+        let header = Row::Section(section);
+        self.index = self.rows.iter().position(|row| *row == header).unwrap_or(0);
+    }
+
+    /// Moves the cursor to the next or previous section header, wrapping
+    /// around at either end.
+    fn jump_section(&mut self, forward: bool) {
+        let headers: Vec<usize> = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| matches!(row, Row::Section(_)))
+            .map(|(position, _)| position)
+            .collect();
+        let target = if forward {
+            headers.iter().find(|&&position| position > self.index)
+        } else {
+            headers
+                .iter()
+                .rev()
+                .find(|&&position| position < self.index)
+        };
+        let wrapped = if forward {
+            headers.first()
+        } else {
+            headers.last()
+        };
+        if let Some(&position) = target.or(wrapped) {
+            self.index = position;
+        }
+    }
+
+    fn current_description(&self) -> Option<&str> {
+        self.current_item()
+            .map(|idx| self.items[idx].tool.description.as_str())
+    }
+
+    fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+        self.refresh_visible();
+        // Land on the best match rather than its section header.
+        self.index = self
+            .rows
+            .iter()
+            .position(|row| matches!(row, Row::Item(_)))
+            .unwrap_or(0);
+    }
+
+    /// Recomputes which items match the search, ranking the best matches
+    /// by name, label or description first.
+    fn refresh_visible(&mut self) {
+        let mut scored: Vec<(i32, usize)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, item)| {
+                let tool = &item.tool;
+                fuzzy::best(&self.filter, &[&tool.name, &tool.label, &tool.description])
+                    .map(|score| (score, idx))
+            })
+            .collect();
+        scored.sort_by_key(|&(score, idx)| (std::cmp::Reverse(score), idx));
+        self.visible = scored.into_iter().map(|(_, idx)| idx).collect();
+
+        self.rows.clear();
+        for section in self.sections() {
+            let members = self.section_items(&section);
+            if members.is_empty() {
+                continue;
+            }
+            let collapsed = self.collapsed.contains(&section);
+            self.rows.push(Row::Section(section));
+            if !collapsed {
+                self.rows.extend(members.into_iter().map(Row::Item));
+            }
+        }
+        self.index = self.index.min(self.rows.len().saturating_sub(1));
+    }
+
+    fn selected_labels(&self) -> Vec<String> {
+        self.targets()
+            .iter()
+            .filter(|item| item.selected)
+            .map(|item| item.tool.label.clone())
+            .collect()
+    }
+}
+
+struct LoadedBrewfile {
+    items: Vec<ToolState>,
+    diagnostics: Vec<Diagnostic>,
+    note: Option<String>,
+    contents: String,
+    /// Set when the Brewfile came from a local file rather than a URL.
+    path: Option<PathBuf>,
+}
+
+struct BrewfileText {
+    contents: String,
+    note: Option<String>,
+    path: Option<PathBuf>,
+}
+
+/// Loads and parses the Brewfile named by `source`, a path or URL, or by
+/// the environment when it is `None`.
+fn load_tools_from_brewfile(source: Option<&str>) -> Result<LoadedBrewfile, String> {
+    let BrewfileText {
+        contents,
+        note,
+        path,
+    } = load_brewfile_text(source)?;
+    let (items, diagnostics) = parse_brewfile(&contents);
+    if items.is_empty() {
+        let mut message =
+            "Brewfile did not contain any tap/brew/cask/mas/vscode/go entries".to_string();
+        if let Some(first) = diagnostics.first() {
+            message.push_str(&format!(" (line {first})"));
+        }
+        Err(message)
+    } else {
+        Ok(LoadedBrewfile {
+            items,
+            diagnostics,
+            note,
+            contents,
+            path,
+        })
+    }
+}
+
+fn load_brewfile_text(source: Option<&str>) -> Result<BrewfileText, String> {
+    if let Some(source) = source {
+        let trimmed = source.trim();
+        if trimmed.is_empty() {
+            return Err("--brewfile was given an empty path".to_string());
+        }
+        return Ok(BrewfileText {
+            contents: load_spec(trimmed)?,
+            note: Some(format!("Loaded Brewfile from {trimmed}")),
+            path: (!looks_like_url(trimmed)).then(|| expand_home(trimmed)),
+        });
+    }
+
+    if let Ok(source) = std::env::var(BREWFILE_SOURCE_ENV) {
+        let trimmed = source.trim();
+        if trimmed.is_empty() {
+            return Err(format!("{BREWFILE_SOURCE_ENV} was set but empty"));
+        }
+        let text = load_spec(trimmed)?;
+        return Ok(BrewfileText {
+            contents: text,
+            note: Some(format!("Loaded Brewfile from {trimmed}")),
+            path: (!looks_like_url(trimmed)).then(|| expand_home(trimmed)),
+        });
+    }
+
+    if let Ok(path) = std::env::var(BREWFILE_PATH_ENV) {
+        let trimmed = path.trim();
+        if trimmed.is_empty() {
+            return Err(format!("{BREWFILE_PATH_ENV} was set but empty"));
+        }
+        let text = load_from_path(trimmed)?;
+        return Ok(BrewfileText {
+            contents: text,
+            note: Some(format!("Loaded Brewfile from {trimmed}")),
+            path: Some(expand_home(trimmed)),
+        });
+    }
+
+    if let Ok(url) = std::env::var(BREWFILE_URL_ENV) {
+        let trimmed = url.trim();
+        if trimmed.is_empty() {
+            return Err(format!("{BREWFILE_URL_ENV} was set but empty"));
+        }
+        let text = fetch_brewfile(trimmed)?;
+        return Ok(BrewfileText {
+            contents: text,
+            note: Some(format!("Loaded Brewfile from {trimmed}")),
+            path: None,
+        });
+    }
+
+    if let Some(path) = find_local_brewfile()? {
+        let display = path.display().to_string();
+        let text = fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read Brewfile at {display}: {err}"))?;
+        return Ok(BrewfileText {
+            contents: text,
+            note: Some(format!("Loaded Brewfile from {display}")),
+            path: Some(path),
+        });
+    }
+
+    let text = fetch_brewfile(DEFAULT_BREWFILE_URL)?;
+    Ok(BrewfileText {
+        contents: text,
+        note: Some(format!(
+            "Loaded Brewfile from {DEFAULT_BREWFILE_URL}. Override with {BREWFILE_SOURCE_ENV}, {BREWFILE_PATH_ENV}, or {BREWFILE_URL_ENV}."
+        )),
+        path: None,
+    })
+}
+
+fn load_spec(spec: &str) -> Result<String, String> {
+    if looks_like_url(spec) {
+        fetch_brewfile(spec)
+    } else {
+        load_from_path(spec)
+    }
+}
+
+fn load_from_path(spec: &str) -> Result<String, String> {
+    let path = expand_home(spec);
+    let display = path.display().to_string();
+    fs::read_to_string(&path)
+        .map_err(|err| format!("Failed to read Brewfile at {display}: {err}"))
+}
+
+fn fetch_brewfile(url: &str) -> Result<String, String> {
+    let client = Client::builder()
+        .timeout(Duration::from_secs(10))
+        .user_agent("install-tools-tui")
+        .build()
+        .map_err(|err| format!("Failed to build HTTP client: {err}"))?;
+
+    let response = client
+        .get(url)
+        .send()
+        .map_err(|err| format!("Failed to fetch Brewfile from {url}: {err}"))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Request to {url} returned status {}",
+            response.status()
+        ));
+    }
+
+    response
+        .text()
+        .map_err(|err| format!("Failed to read Brewfile contents: {err}"))
+}
+
+fn looks_like_url(spec: &str) -> bool {
+    let lower = spec.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+fn expand_home(spec: &str) -> PathBuf {
+    if let Some(rest) = spec.strip_prefix("~/") {
+        if let Ok(home) = std::env::var("HOME") {
+            return Path::new(&home).join(rest);
+        }
+    } else if spec == "~"
+        && let Ok(home) = std::env::var("HOME")
+    {
+        return PathBuf::from(home);
+    }
+    PathBuf::from(spec)
+}
+
+fn find_local_brewfile() -> Result<Option<PathBuf>, String> {
+    let mut dir = std::env::current_dir()
+        .map_err(|err| format!("Failed to determine current directory: {err}"))?;
+    loop {
+        let candidate = dir.join("Brewfile");
+        if candidate.is_file() {
+            return Ok(Some(candidate));
+        }
+        if !dir.pop() {
+            break;
+        }
+    }
+    Ok(None)
+}
+
+fn parse_brewfile(contents: &str) -> (Vec<ToolState>, Vec<Diagnostic>) {
+    let brewfile = brewfile::parse(contents, &Host::current());
+    let mut tools = Vec::new();
+    // Taps declared anywhere in the file, so a qualified formula listed
+    // before its `tap` line does not imply a second entry for it.
+    let mut seen: HashSet<_> = brewfile
+        .entries
+        .iter()
+        .filter(|entry| entry.kind == EntryKind::Tap && entry.excluded.is_none())
+        .map(|entry| (ToolKind::Tap, entry.name.to_ascii_lowercase()))
+        .collect();
+
+    for entry in brewfile.entries {
+        let name = entry.name.clone();
+        let tool = match entry.kind {
+            EntryKind::Tap => {
+                let description = entry
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| format!("Homebrew tap '{name}'"));
+                let mut state = tap_tool_state(&name, description);
+                state.tool.options.tap_url = entry.extra.first().cloned();
+                state.tool.category = entry.category.clone();
+                state.excluded = entry.excluded.clone();
+                state.origin = Origin::Line(entry.line);
+                tools.push(state);
+                continue;
+            }
+            EntryKind::Brew => {
+                if entry.excluded.is_none() {
+                    push_implied_tap(&mut tools, &mut seen, &entry);
+                }
+                Tool {
+                    kind: ToolKind::BrewFormula,
+                    label: format!("{name} (brew formula)"),
+                    description: entry
+                        .description
+                        .clone()
+                        .unwrap_or_else(|| format!("Homebrew formula '{name}'")),
+                    category: entry.category.clone(),
+                    fallbacks: Vec::new(),
+                    app_id: None,
+                    version: None,
+                    options: BrewOptions::from_entry(&entry),
+                    name,
+                }
+            }
+            EntryKind::Cask => {
+                if entry.excluded.is_none() {
+                    push_implied_tap(&mut tools, &mut seen, &entry);
+                }
+                Tool {
+                    kind: ToolKind::BrewCask,
+                    label: format!("{name} (cask)"),
+                    description: entry
+                        .description
+                        .clone()
+                        .unwrap_or_else(|| format!("Homebrew cask '{name}'")),
+                    category: entry.category.clone(),
+                    fallbacks: Vec::new(),
+                    app_id: None,
+                    version: None,
+                    options: BrewOptions::from_entry(&entry),
+                    name,
+                }
+            }
+            EntryKind::Mas => {
+                let app_id = entry
+                    .option("id")
+                    .and_then(OptionValue::as_str)
+                    .unwrap_or_default()
+                    .to_string();
+                Tool {
+                    kind: ToolKind::Mas,
+                    label: format!("{name} (App Store)"),
+                    description: entry
+                        .description
+                        .clone()
+                        .unwrap_or_else(|| format!("Mac App Store app '{name}' (id {app_id})")),
+                    category: entry.category.clone(),
+                    fallbacks: Vec::new(),
+                    app_id: Some(app_id),
+                    version: None,
+                    options: BrewOptions::default(),
+                    name,
+                }
+            }
+            EntryKind::Vscode => Tool {
+                kind: ToolKind::VsCode,
+                label: format!("{name} (VS Code)"),
+                description: entry
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| format!("VS Code extension '{name}'")),
+                category: entry.category.clone(),
+                fallbacks: Vec::new(),
+                app_id: None,
+                version: None,
+                options: BrewOptions::default(),
+                name,
+            },
+            EntryKind::Go => {
+                let (path, version) = split_go_version(&name);
+                let binary = go_binary_name(&path);
+                Tool {
+                    kind: ToolKind::Go,
+                    label: format!("{binary} (go)"),
+                    description: entry
+                        .description
+                        .clone()
+                        .unwrap_or_else(|| format!("Go binary '{binary}' from {path}")),
+                    category: entry.category.clone(),
+                    fallbacks: Vec::new(),
+                    app_id: None,
+                    version,
+                    options: BrewOptions::default(),
+                    name: path,
+                }
+            }
+        };
+        tools.push(ToolState {
+            tool,
+            selected: false,
+            status: None,
+            excluded: entry.excluded.clone(),
+            origin: Origin::Line(entry.line),
+            log: Vec::new(),
+            duration: None,
+        });
+    }
+
+    (tools, brewfile.diagnostics)
+}
+
+/// Applies a key press to a single-line text input.
+fn edit_input(input: &mut String, key: KeyEvent) {
+    match key.code {
+        KeyCode::Backspace => {
+            input.pop();
+        }
+        KeyCode::Char(ch) => input.push(ch),
+        _ => {}
+    }
+}
+
+fn tap_tool_state(name: &str, description: String) -> ToolState {
+    ToolState {
+        tool: Tool {
+            kind: ToolKind::Tap,
+            name: name.to_string(),
+            label: format!("{name} (tap)"),
+            description,
+            category: None,
+            fallbacks: Vec::new(),
+            app_id: None,
+            version: None,
+            options: BrewOptions::default(),
+        },
+        selected: false,
+        status: None,
+        excluded: None,
+        origin: Origin::Implied,
+        log: Vec::new(),
+        duration: None,
+    }
+}
+
+/// Adds a tap entry for a fully qualified `owner/repo/name` formula or cask
+/// when the Brewfile did not declare that tap itself. The tap joins the
+/// entry's section so it is grouped with what needs it.
+fn push_implied_tap(
+    tools: &mut Vec<ToolState>,
+    seen: &mut HashSet<(ToolKind, String)>,
+    entry: &Entry,
+) {
+    let name = &entry.name;
+    if let Some(tap) = qualified_tap(name)
+        && seen.insert((ToolKind::Tap, tap.to_ascii_lowercase()))
+    {
+        let description = format!("Homebrew tap '{tap}' (required by {name})");
+        let mut state = tap_tool_state(&tap, description);
+        state.tool.category = entry.category.clone();
+        tools.push(state);
+    }
+}
+
+/// Returns the `owner/repo` tap for a fully qualified `owner/repo/name`.
+fn qualified_tap(name: &str) -> Option<String> {
+    let mut parts = name.split('/');
+    let (owner, repo, formula) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || owner.is_empty() || repo.is_empty() || formula.is_empty() {
+        return None;
+    }
+    Some(format!("{owner}/{repo}"))
+}
+
+/// Splits `module/path@version` into the path and an optional pinned version.
+fn split_go_version(spec: &str) -> (String, Option<String>) {
+    match spec.rsplit_once('@') {
+        Some((path, version)) if !version.is_empty() => {
+            (path.to_string(), Some(version.to_string()))
+        }
+        Some((path, _)) => (path.to_string(), None),
+        None => (spec.to_string(), None),
+    }
+}
+
+/// Resolves the binary `go install` produces for a package path: its last
+/// element, skipping a trailing major-version suffix such as `/v2`.
+fn go_binary_name(path: &str) -> String {
+    let mut segments = path.trim_end_matches('/').rsplit('/');
+    let last = segments.next().unwrap_or(path);
+    let is_major_version =
+        last.len() > 1 && last.starts_with('v') && last[1..].chars().all(|ch| ch.is_ascii_digit());
+    if is_major_version && let Some(parent) = segments.next() {
+        return parent.to_string();
+    }
+    last.to_string()
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    match app.mode {
+        Mode::Selecting => draw_selection(frame, app),
+        Mode::Confirm => draw_confirm(frame, app),
+        Mode::Results => draw_results(frame, app),
+        Mode::Log => draw_log(frame, app),
+        Mode::History => draw_history(frame, app),
+        Mode::Diagnostics => draw_diagnostics(frame, app),
+        Mode::Save => draw_input(frame, app, "Save selection as Brewfile", &save_hint(app)),
+        Mode::Export => draw_input(
+            frame,
+            app,
+            "Export installed software as a Brewfile",
+            &export_hint(app),
+        ),
+        Mode::Drift => draw_drift(frame, app),
+        Mode::Cleanup => draw_operation_list(
+            frame,
+            app,
+            &format!(
+                "Installed but not in the Brewfile ({} item(s)) - select what to remove:",
+                app.removals.len()
+            ),
+            "[space - Toggle Selection] [a - Select All] [d - Deselect All] [enter - Remove Selected] [esc - Back to Selection]",
+        ),
+        Mode::Upgrade => draw_operation_list(
+            frame,
+            app,
+            &format!(
+                "{} outdated item(s){} - select what to upgrade:",
+                app.upgrades.len(),
+                if app.greedy_upgrades {
+                    " including auto-updating casks"
+                } else {
+                    ""
+                }
+            ),
+            "[space - Toggle Selection] [a - Select All] [d - Deselect All] [g - Toggle Greedy Casks] [enter - Upgrade Selected] [esc - Back to Selection]",
+        ),
+        Mode::AddEntry => draw_input(
+            frame,
+            app,
+            "Add a Brewfile entry",
+            "Type a Brewfile line, e.g. brew \"ripgrep\" or cask \"firefox\". [enter - Add] [esc - Cancel]",
+        ),
+    }
+}
+
+fn save_hint(app: &App) -> String {
+    let path = expand_home(app.input.trim());
+    let note = if app.brewfile_path.as_deref() == Some(path.as_path()) {
+        "This is the loaded Brewfile; saving asks before overwriting it."
+    } else if path.exists() {
+        "File exists; saving asks before overwriting it."
+    } else {
+        "Creates a new Brewfile."
+    };
+    format!(
+        "{note} Unselected entries are dropped; comments and grouping are kept. [enter - Save] [esc - Cancel]"
+    )
+}
+
+fn export_hint(app: &App) -> String {
+    let path = expand_home(app.input.trim());
+    let note = if path.exists() {
+        "File exists and will be overwritten."
+    } else {
+        "Creates a new Brewfile."
+    };
+    format!(
+        "{note} Lists taps, brew leaves, casks, App Store apps and VS Code extensions found on this machine. [enter - Export] [esc - Cancel]"
+    )
+}
+
+fn draw_input(frame: &mut Frame, app: &App, title: &str, hint: &str) {
+    let area = frame.size();
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(0),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let title = Paragraph::new(title.to_string())
+        .block(Block::default().borders(Borders::NONE))
+        .alignment(Alignment::Center)
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    frame.render_widget(title, chunks[0]);
+
+    let input =
+        Paragraph::new(format!("{}_", app.input)).block(Block::default().borders(Borders::ALL));
+    frame.render_widget(input, chunks[1]);
+
+    let mut lines = vec![Line::styled(
+        hint.to_string(),
+        Style::default().fg(Color::Gray),
+    )];
+    if let Some(info) = &app.info {
+        lines.push(Line::styled(
+            info.clone(),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    let footer = Paragraph::new(lines).wrap(Wrap { trim: true });
+    frame.render_widget(footer, chunks[2]);
+}
+
+fn draw_selection(frame: &mut Frame, app: &App) {
+    let mut title_lines = vec![Line::styled(
+        "Select what tools you want:",
+        Style::default().add_modifier(Modifier::BOLD),
+    )];
+    if !app.diagnostics.is_empty() {
+        let ignored = app.ignored_line_count();
+        let warnings = app.diagnostics.len() - ignored;
+        let mut notice = format!("{ignored} Brewfile line(s) ignored");
+        if warnings > 0 {
+            notice.push_str(&format!(", {warnings} warning(s)"));
+        }
+        notice.push_str(" - press w to view");
+        title_lines.push(Line::styled(notice, Style::default().fg(Color::Yellow)));
+    }
+    if app.probe.is_some() {
+        title_lines.push(Line::styled(
+            "Checking what is already installed...",
+            Style::default().fg(Color::Gray),
+        ));
+    } else if let Some(warning) = app
+        .installed
+        .as_ref()
+        .and_then(|installed| installed.warnings.first())
+    {
+        title_lines.push(Line::styled(
+            format!("Installed state may be incomplete: {warning}"),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if app.filtering || !app.filter.is_empty() {
+        let cursor = if app.filtering { "_" } else { "" };
+        title_lines.push(Line::styled(
+            format!(
+                "/{}{cursor}  ({} of {} match)",
+                app.filter,
+                app.visible.len(),
+                app.items.len()
+            ),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+    let area = frame.size();
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Length((title_lines.len() as u16).max(3)),
+                Constraint::Min(5),
+                Constraint::Length(2),
+                Constraint::Length(3),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let title = Paragraph::new(title_lines)
+        .block(Block::default().borders(Borders::NONE))
+        .alignment(Alignment::Center);
+    frame.render_widget(title, chunks[0]);
+
+    let items: Vec<ListItem> = app
+        .rows
+        .iter()
+        .map(|row| {
+            let idx = match row {
+                Row::Section(section) => return section_header(app, section),
+                Row::Item(idx) => *idx,
+            };
+            let item = &app.items[idx];
+            if let Some(reason) = &item.excluded {
+                return ListItem::new(format!("  [-] {} - skipped: {reason}", item.tool.label))
+                    .style(Style::default().fg(Color::DarkGray));
+            }
+            let marker = if item.selected { "[x]" } else { "[ ]" };
+            let mut spans = vec![Span::raw(format!("  {marker} {}", item.tool.label))];
+            if let Some(version) = app.installed_version(&item.tool) {
+                let badge = if version.is_empty() {
+                    "  installed".to_string()
+                } else {
+                    format!("  installed {version}")
+                };
+                spans.push(Span::styled(badge, Style::default().fg(Color::Green)));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default();
+    state.select(Some(app.index));
+    let list_area = if app.show_details && chunks[1].width >= 80 {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+            .split(chunks[1]);
+        draw_details(frame, app, columns[1]);
+        columns[0]
+    } else {
+        chunks[1]
+    };
+    frame.render_stateful_widget(list, list_area, &mut state);
+
+    let instructions = Paragraph::new(if app.filtering {
+        "Type to search names and descriptions. [enter - Keep Filter] [esc - Clear Filter] [up/down - Move]"
+    } else if !app.filter.is_empty() {
+        "[space - Toggle Selection] [a - Select Matching] [d - Deselect Matching] [t - Toggle Section] [tab - Next Section] [/ - Edit Search] [esc - Clear Search] [enter - Install Selected Tools] [q - Quit]"
+    } else {
+        "[space - Toggle Selection] [a - Select All] [d - Deselect All] [t - Toggle Section] [c - Collapse Section] [tab - Next Section] [g - Group by Kind/Section] [m - Select Missing] [i - Toggle Details] [/ - Search] [enter - Install Selected Tools] [+ - Add Entry] [s - Save Brewfile] [e - Export Installed] [r - Compare Installed] [x - Remove Unlisted] [u - Upgrade Outdated] [h - Run History] [w - Parse Warnings] [q - Quit]"
+    })
+    .wrap(Wrap { trim: true })
+    .style(Style::default().fg(Color::Gray));
+    frame.render_widget(instructions, chunks[2]);
+
+    let mut lines = Vec::new();
+    if let Some(info) = &app.info {
+        lines.push(Line::styled(
+            info.clone(),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if let Some(desc) = app.current_description() {
+        lines.push(Line::styled(
+            desc.to_string(),
+            Style::default().fg(Color::Gray),
+        ));
+    }
+    if lines.is_empty() {
+        lines.push(Line::raw(""));
+    }
+    let footer = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::NONE))
+        .wrap(Wrap { trim: true });
+    frame.render_widget(footer, chunks[3]);
+}
+
+/// Side pane describing the highlighted entry: its Brewfile description
+/// and section, then what `brew info` or the App Store reports once fetched.
+fn draw_details(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title("Details");
+    let Some(item) = app.current_item().map(|idx| &app.items[idx]) else {
+        let hint = Paragraph::new("Space selects every entry of this section.")
+            .style(Style::default().fg(Color::Gray))
+            .wrap(Wrap { trim: true })
+            .block(block);
+        frame.render_widget(hint, area);
+        return;
+    };
+    let tool = &item.tool;
+    let label = Style::default().add_modifier(Modifier::BOLD);
+    let mut lines = vec![
+        Line::styled(tool.label.clone(), label),
+        Line::styled(tool.description.clone(), Style::default().fg(Color::Gray)),
+        Line::raw(""),
+    ];
+    let mut field = |name: &str, value: String| {
+        lines.push(Line::from(vec![
+            Span::styled(format!("{name}: "), label),
+            Span::raw(value),
+        ]));
+    };
+    if let Some(category) = &tool.category {
+        field("Section", category.clone());
+    }
+    if let Origin::Line(line) = item.origin {
+        field("Brewfile line", line.to_string());
+    }
+    match app.details.get(&detail_key(tool)) {
+        Some(Lookup::Ready(details)) => {
+            for (name, value) in &details.fields {
+                field(name, value.clone());
+            }
+            if let Some(caveats) = &details.caveats {
+                lines.push(Line::raw(""));
+                lines.push(Line::styled("Caveats:", label));
+                lines.extend(caveats.lines().map(|line| Line::raw(line.to_string())));
+            }
+        }
+        Some(Lookup::Failed(err)) => {
+            lines.push(Line::styled(err.clone(), Style::default().fg(Color::Red)));
+        }
+        Some(Lookup::Loading) | None => {
+            lines.push(Line::styled(
+                "Loading details...",
+                Style::default().fg(Color::Gray),
+            ));
+        }
+    }
+    let pane = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(block);
+    frame.render_widget(pane, area);
+}
+
+/// Header of a section with how many of its items are selected, and how
+/// many match while searching.
+fn section_header(app: &App, section: &Section) -> ListItem<'static> {
+    let members: Vec<&ToolState> = app
+        .items
+        .iter()
+        .filter(|item| section.contains(&item.tool))
+        .collect();
+    let selected = members.iter().filter(|item| item.selected).count();
+    let installed = app.installed.is_some().then(|| {
+        members
+            .iter()
+            .filter(|item| app.installed_version(&item.tool).is_some())
+            .count()
+    });
+    let fold = if app.collapsed.contains(section) {
+        "▸"
+    } else {
+        "▾"
+    };
+    let mut counts = vec![format!("{selected}/{} selected", members.len())];
+    if let Some(installed) = installed {
+        counts.push(format!("{installed} installed"));
+    }
+    if !app.filter.is_empty() {
+        counts.push(format!("{} matching", app.section_items(section).len()));
+    }
+    let text = format!("{fold} {} ({})", section.title(), counts.join(", "));
+    ListItem::new(text).style(
+        Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD),
+    )
+}
+
+fn draw_confirm(frame: &mut Frame, app: &App) {
+    let area = frame.size();
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Min(5),
+                Constraint::Length(2),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let title_text = format!(
+        "{} {} tool(s)? (y/n)",
+        app.operation.verb(),
+        app.selected_count()
+    );
+    let title = Paragraph::new(title_text)
+        .block(Block::default().borders(Borders::NONE))
+        .alignment(Alignment::Center)
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    frame.render_widget(title, chunks[0]);
+
+    let selected = app.selected_labels();
+    let list_lines: Vec<Line> = selected
+        .into_iter()
+        .map(|label| Line::raw(format!("- {label}")))
+        .collect();
+    let list_block = Paragraph::new(list_lines)
+        .block(Block::default().borders(Borders::ALL))
+        .wrap(Wrap { trim: true });
+    frame.render_widget(list_block, chunks[1]);
+
+    let instruction = Paragraph::new("Press y to confirm, n to go back, q to quit.")
+        .style(Style::default().fg(Color::Gray));
+    frame.render_widget(instruction, chunks[2]);
+}
+
+fn draw_results(frame: &mut Frame, app: &App) {
+    let area = frame.size();
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Percentage(45),
+                Constraint::Min(5),
+                Constraint::Length(2),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let title_text = match app.operation {
+        Operation::Install => "Select what tools you want:",
+        Operation::Uninstall => "Removing software not listed in the Brewfile:",
+        Operation::Upgrade => "Upgrading outdated software:",
+    };
+    let title = Paragraph::new(title_text)
+        .block(Block::default().borders(Borders::NONE))
+        .alignment(Alignment::Center)
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    frame.render_widget(title, chunks[0]);
+
+    let items: Vec<ListItem> = app
+        .targets()
+        .iter()
+        .map(|item| {
+            let marker = if !item.selected {
+                "[ ]".to_string()
+            } else if let Some(status) = &item.status {
+                status_marker(status, &app.symbols, app.tick).to_string()
+            } else {
+                "[x]".to_string()
+            };
+
+            let mut lines = Vec::new();
+            let mut spans = Vec::new();
+            spans.push(Span::raw(format!("{marker} {}", item.tool.label)));
+            if let Some(status) = &item.status {
+                let style = status_style(status);
+                spans.push(Span::raw(" "));
+                spans.push(Span::styled(
+                    status_label(status, &item.tool.kind, app.operation),
+                    style,
+                ));
+            }
+            lines.push(Line::from(spans));
+
+            if let Some(status) = &item.status {
+                let message = status.message();
+                if !message.is_empty() {
+                    lines.push(Line::styled(
+                        format!("    - {message}"),
+                        Style::default().fg(Color::Gray),
+                    ));
+                }
+            }
+
+            ListItem::new(lines)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default();
+    state.select(Some(app.results_index));
+    frame.render_stateful_widget(list, chunks[1], &mut state);
+
+    draw_output_pane(frame, app, chunks[2]);
+
+    let footer_text = app
+        .progress
+        .clone()
+        .unwrap_or_else(|| "Press Enter or q to exit.".to_string());
+    let footer = Paragraph::new(vec![
+        Line::raw(footer_text),
+        Line::raw(
+            "[j/k - Select Item] [PgUp/PgDn - Scroll Output] [f - Follow Running Item] [enter - Full Log]",
+        ),
+    ])
+    .style(Style::default().fg(Color::Gray));
+    frame.render_widget(footer, chunks[3]);
+}
+
+/// The captured output of the item under the results cursor, pinned to
+/// the newest line unless scrolled back.
+fn draw_output_pane(frame: &mut Frame, app: &App, area: Rect) {
+    let item = app.targets().get(app.results_index);
+    let title = item.map_or_else(
+        || "Output".to_string(),
+        |item| format!("Output: {}", item.tool.label),
+    );
+    let lines = item.map(|item| log_lines(&item.log)).unwrap_or_default();
+    let visible = usize::from(area.height.saturating_sub(2));
+    let bottom = lines.len().saturating_sub(visible);
+    let top = app.log_scroll.map_or(bottom, |top| top.min(bottom));
+    let pane = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .scroll((u16::try_from(top).unwrap_or(u16::MAX), 0));
+    frame.render_widget(pane, area);
+}
+
+fn log_lines(log: &[LogEvent]) -> Vec<Line<'static>> {
+    log_entries(log)
+        .into_iter()
+        .map(|(text, style)| Line::styled(text, style))
+        .collect()
+}
+
+/// The text and style of each log line, shared by the output pane, the
+/// log viewer and its search.
+fn log_entries(log: &[LogEvent]) -> Vec<(String, Style)> {
+    log.iter()
+        .map(|event| match event {
+            LogEvent::Command(command) => (
+                format!("$ {command}"),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            LogEvent::Output {
+                line,
+                stderr: false,
+            } => (line.clone(), Style::default()),
+            LogEvent::Output { line, stderr: true } => {
+                (line.clone(), Style::default().fg(Color::LightRed))
+            }
+            LogEvent::Exit { code, duration } => (
+                format!(
+                    "exit {} after {}",
+                    exit_code_label(*code),
+                    format_duration(*duration)
+                ),
+                Style::default().fg(Color::DarkGray),
+            ),
+        })
+        .collect()
+}
+
+fn exit_code_label(code: Option<i32>) -> String {
+    code.map_or_else(|| "signal".to_string(), |code| code.to_string())
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.1}s", duration.as_secs_f64())
+}
+
+/// Full-screen view of one item's log: the commands it ran with their exit
+/// codes and timings, then every line of output.
+fn draw_log(frame: &mut Frame, app: &App) {
+    let Some(item) = app.targets().get(app.results_index) else {
+        return;
+    };
+    let commands: Vec<Line> = item
+        .log
+        .iter()
+        .filter_map(|event| match event {
+            LogEvent::Command(command) => Some(Line::raw(format!("$ {command}"))),
+            LogEvent::Exit { code, duration } => Some(Line::styled(
+                format!(
+                    "    exit {} after {}",
+                    exit_code_label(*code),
+                    format_duration(*duration)
+                ),
+                Style::default().fg(Color::Gray),
+            )),
+            LogEvent::Output { .. } => None,
+        })
+        .collect();
+
+    let area = frame.size();
+    let header_height = u16::try_from(commands.len().clamp(1, 8)).unwrap_or(8) + 2;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Length(2),
+                Constraint::Length(header_height),
+                Constraint::Min(5),
+                Constraint::Length(2),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let mut title = vec![Span::styled(
+        item.tool.label.clone(),
+        Style::default().add_modifier(Modifier::BOLD),
+    )];
+    if let Some(status) = &item.status {
+        title.push(Span::raw(" "));
+        title.push(Span::styled(
+            status_label(status, &item.tool.kind, app.operation),
+            status_style(status),
+        ));
+    }
+    if let Some(duration) = item.duration {
+        title.push(Span::raw(format!(" in {}", format_duration(duration))));
+    }
+    frame.render_widget(
+        Paragraph::new(Line::from(title)).alignment(Alignment::Center),
+        chunks[0],
+    );
+
+    let commands = if commands.is_empty() {
+        vec![Line::styled(
+            "No commands were run.",
+            Style::default().fg(Color::Gray),
+        )]
+    } else {
+        commands
+    };
+    frame.render_widget(
+        Paragraph::new(commands).block(Block::default().borders(Borders::ALL).title("Commands")),
+        chunks[1],
+    );
+
+    let matches = app.log_matches();
+    let lines: Vec<Line> = log_entries(&item.log)
+        .into_iter()
+        .enumerate()
+        .map(|(index, (text, style))| {
+            let style = if matches.contains(&index) {
+                style.bg(Color::DarkGray)
+            } else {
+                style
+            };
+            Line::styled(text, style)
+        })
+        .collect();
+    let total = lines.len();
+    let top = app.log_view_scroll.min(total.saturating_sub(1));
+    let log = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Output ({total} lines)")),
+        )
+        .scroll((u16::try_from(top).unwrap_or(u16::MAX), 0));
+    frame.render_widget(log, chunks[2]);
+
+    let footer = if app.log_searching {
+        Line::raw(format!("/{}_", app.input))
+    } else if !app.log_search.is_empty() {
+        Line::styled(
+            format!(
+                "{} match(es) for \"{}\" [n/N - Next/Previous] [/ - Search] [j/k PgUp/PgDn g/G - Scroll] [esc - Back]",
+                matches.len(),
+                app.log_search
+            ),
+            Style::default().fg(Color::Gray),
+        )
+    } else {
+        Line::styled(
+            "[/ - Search] [j/k PgUp/PgDn g/G - Scroll] [esc - Back]",
+            Style::default().fg(Color::Gray),
+        )
+    };
+    frame.render_widget(Paragraph::new(footer).wrap(Wrap { trim: true }), chunks[3]);
+}
+
+fn draw_history(frame: &mut Frame, app: &App) {
+    let area = frame.size();
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Min(5),
+                Constraint::Length(2),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let title = Paragraph::new(format!("Past runs ({})", app.history.len()))
+        .block(Block::default().borders(Borders::NONE))
+        .alignment(Alignment::Center)
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    frame.render_widget(title, chunks[0]);
+
+    let items: Vec<ListItem> = app
+        .history
+        .iter()
+        .map(|run| {
+            let mut spans = vec![Span::raw(format!(
+                "{}  {:<9} {} item(s)",
+                run.started,
+                run.operation.name(),
+                run.total
+            ))];
+            if run.failed > 0 {
+                spans.push(Span::styled(
+                    format!(", {} failed", run.failed),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default();
+    state.select(Some(app.history_index));
+    frame.render_stateful_widget(list, chunks[1], &mut state);
+
+    let mut lines = vec![Line::styled(
+        "[j/k - Move] [enter - Open Results] [esc - Back to Selection]",
+        Style::default().fg(Color::Gray),
+    )];
+    if let Some(info) = &app.info {
+        lines.push(Line::styled(
+            info.clone(),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    frame.render_widget(Paragraph::new(lines), chunks[2]);
+}
+
+fn draw_diagnostics(frame: &mut Frame, app: &App) {
+    let area = frame.size();
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Min(5),
+                Constraint::Length(2),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let title = Paragraph::new(format!(
+        "Brewfile parse warnings ({} line(s) ignored)",
+        app.ignored_line_count()
+    ))
+    .block(Block::default().borders(Borders::NONE))
+    .alignment(Alignment::Center)
+    .style(Style::default().add_modifier(Modifier::BOLD));
+    frame.render_widget(title, chunks[0]);
+
+    let items: Vec<ListItem> = app
+        .diagnostics
+        .iter()
+        .map(|diag| {
+            let (label, style) = match diag.severity {
+                Severity::Ignored => ("ignored", Style::default().fg(Color::Red)),
+                Severity::Warning => ("warning", Style::default().fg(Color::Yellow)),
+            };
+            ListItem::new(vec![
+                Line::from(vec![
+                    Span::styled(label, style),
+                    Span::raw(format!(
+                        " line {}:{} {}",
+                        diag.line, diag.column, diag.message
+                    )),
+                ]),
+                Line::styled(
+                    format!("    {}", diag.source.trim_end()),
+                    Style::default().fg(Color::Gray),
+                ),
+            ])
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default();
+    state.select(Some(app.diagnostics_index));
+    frame.render_stateful_widget(list, chunks[1], &mut state);
+
+    let instruction = Paragraph::new("[j/k - Scroll] [esc - Back to Selection]")
+        .style(Style::default().fg(Color::Gray));
+    frame.render_widget(instruction, chunks[2]);
 }
 
-fn draw_selection(frame: &mut Frame, app: &App) {
+fn draw_drift(frame: &mut Frame, app: &App) {
     let area = frame.size();
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -571,29 +3218,46 @@ fn draw_selection(frame: &mut Frame, app: &App) {
         )
         .split(area);
 
-    let title = Paragraph::new("Select what tools you want:")
-        .block(Block::default().borders(Borders::NONE))
-        .alignment(Alignment::Center)
-        .style(Style::default().add_modifier(Modifier::BOLD));
+    let count = |state| app.drift.iter().filter(|item| item.state == state).count();
+    let title = Paragraph::new(format!(
+        "Brewfile drift: {} missing, {} extra, {} installed",
+        count(DriftState::Missing),
+        count(DriftState::Extra),
+        count(DriftState::Installed)
+    ))
+    .block(Block::default().borders(Borders::NONE))
+    .alignment(Alignment::Center)
+    .style(Style::default().add_modifier(Modifier::BOLD));
     frame.render_widget(title, chunks[0]);
 
     let items: Vec<ListItem> = app
-        .items
+        .drift
         .iter()
         .map(|item| {
-            let marker = if item.selected { "[x]" } else { "[ ]" };
-            ListItem::new(format!("{marker} {}", item.tool.label))
+            let marker = match item.state {
+                DriftState::Installed => "   ",
+                _ if item.selected => "[x]",
+                _ => "[ ]",
+            };
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{marker} ")),
+                Span::styled(
+                    format!("{:<9}", item.state.label()),
+                    drift_style(item.state),
+                ),
+                Span::raw(format!(" {}", item.label)),
+            ]))
         })
         .collect();
     let list = List::new(items)
         .block(Block::default().borders(Borders::ALL))
         .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
     let mut state = ListState::default();
-    state.select(Some(app.index));
+    state.select(Some(app.drift_index));
     frame.render_stateful_widget(list, chunks[1], &mut state);
 
     let instructions = Paragraph::new(
-        "[space - Toggle Selection] [a - Select All] [d - Deselect All] [enter - Install Selected Tools] [q - Quit]",
+        "[space - Toggle Selection] [i - Install Missing] [a - Append Extras to Brewfile] [esc - Back to Selection]",
     )
     .wrap(Wrap { trim: true })
     .style(Style::default().fg(Color::Gray));
@@ -606,14 +3270,12 @@ fn draw_selection(frame: &mut Frame, app: &App) {
             Style::default().fg(Color::Yellow),
         ));
     }
-    if let Some(desc) = app.current_description() {
-        lines.push(Line::styled(
-            desc.to_string(),
-            Style::default().fg(Color::Gray),
-        ));
-    }
-    if lines.is_empty() {
-        lines.push(Line::raw(""));
+    if let Some(line) = app
+        .drift
+        .get(app.drift_index)
+        .and_then(|item| item.entry_line.as_ref())
+    {
+        lines.push(Line::styled(line.clone(), Style::default().fg(Color::Gray)));
     }
     let footer = Paragraph::new(lines)
         .block(Block::default().borders(Borders::NONE))
@@ -621,7 +3283,7 @@ fn draw_selection(frame: &mut Frame, app: &App) {
     frame.render_widget(footer, chunks[3]);
 }
 
-fn draw_confirm(frame: &mut Frame, app: &App) {
+fn draw_operation_list(frame: &mut Frame, app: &App, title: &str, instructions: &str) {
     let area = frame.size();
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -631,106 +3293,65 @@ fn draw_confirm(frame: &mut Frame, app: &App) {
                 Constraint::Length(3),
                 Constraint::Min(5),
                 Constraint::Length(2),
-            ]
-            .as_ref(),
-        )
-        .split(area);
-
-    let title_text = format!("Install {} tool(s)? (y/n)", app.selected_count());
-    let title = Paragraph::new(title_text)
-        .block(Block::default().borders(Borders::NONE))
-        .alignment(Alignment::Center)
-        .style(Style::default().add_modifier(Modifier::BOLD));
-    frame.render_widget(title, chunks[0]);
-
-    let selected = app.selected_labels();
-    let list_lines: Vec<Line> = selected
-        .into_iter()
-        .map(|label| Line::raw(format!("- {label}")))
-        .collect();
-    let list_block = Paragraph::new(list_lines)
-        .block(Block::default().borders(Borders::ALL))
-        .wrap(Wrap { trim: true });
-    frame.render_widget(list_block, chunks[1]);
-
-    let instruction = Paragraph::new("Press y to confirm, n to go back, q to quit.")
-        .style(Style::default().fg(Color::Gray));
-    frame.render_widget(instruction, chunks[2]);
-}
-
-fn draw_results(frame: &mut Frame, app: &App) {
-    let area = frame.size();
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .margin(1)
-        .constraints(
-            [
                 Constraint::Length(3),
-                Constraint::Min(5),
-                Constraint::Length(2),
             ]
             .as_ref(),
         )
         .split(area);
 
-    let title = Paragraph::new("Select what tools you want:")
+    let title = Paragraph::new(title.to_string())
         .block(Block::default().borders(Borders::NONE))
         .alignment(Alignment::Center)
         .style(Style::default().add_modifier(Modifier::BOLD));
     frame.render_widget(title, chunks[0]);
 
     let items: Vec<ListItem> = app
-        .items
+        .targets()
         .iter()
         .map(|item| {
-            let marker = if !item.selected {
-                "[ ]".to_string()
-            } else if let Some(status) = &item.status {
-                status_marker(status, &app.symbols).to_string()
-            } else {
-                "[x]".to_string()
-            };
-
-            let mut lines = Vec::new();
-            let mut spans = Vec::new();
-            spans.push(Span::raw(format!("{marker} {}", item.tool.label)));
-            if let Some(status) = &item.status {
-                let style = status_style(status);
-                spans.push(Span::raw(" "));
-                spans.push(Span::styled(status_label(status), style));
-            }
-            lines.push(Line::from(spans));
-
-            if let Some(status) = &item.status {
-                let message = status.message();
-                if !message.is_empty() {
-                    lines.push(Line::styled(
-                        format!("    - {message}"),
-                        Style::default().fg(Color::Gray),
-                    ));
-                }
-            }
-
-            ListItem::new(lines)
+            let marker = if item.selected { "[x]" } else { "[ ]" };
+            ListItem::new(format!("{marker} {}", item.tool.label))
         })
         .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default();
+    state.select(Some(app.operation_index));
+    frame.render_stateful_widget(list, chunks[1], &mut state);
 
-    let list = List::new(items).block(Block::default().borders(Borders::ALL));
-    frame.render_widget(list, chunks[1]);
+    let instructions = Paragraph::new(instructions.to_string())
+        .wrap(Wrap { trim: true })
+        .style(Style::default().fg(Color::Gray));
+    frame.render_widget(instructions, chunks[2]);
 
-    let footer_text = app
-        .progress
-        .clone()
-        .unwrap_or_else(|| "Press Enter or q to exit.".to_string());
-    let footer = Paragraph::new(footer_text).style(Style::default().fg(Color::Gray));
-    frame.render_widget(footer, chunks[2]);
+    let mut lines = Vec::new();
+    if let Some(info) = &app.info {
+        lines.push(Line::styled(
+            info.clone(),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    let footer = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::NONE))
+        .wrap(Wrap { trim: true });
+    frame.render_widget(footer, chunks[3]);
+}
+
+fn drift_style(state: DriftState) -> Style {
+    match state {
+        DriftState::Missing => Style::default().fg(Color::Red),
+        DriftState::Extra => Style::default().fg(Color::Yellow),
+        DriftState::Installed => Style::default().fg(Color::Green),
+    }
 }
 
-fn status_marker(status: &Status, symbols: &Symbols) -> &'static str {
+fn status_marker(status: &Status, symbols: &Symbols, tick: usize) -> &'static str {
     match status {
-        Status::Pending(_) => symbols.pending,
+        Status::Pending(_) => symbols.spinner[tick % symbols.spinner.len()],
         Status::Success(_) | Status::Skipped(_) => symbols.success,
         Status::Failed(_) => symbols.failure,
+        Status::Cancelled(_) => symbols.cancelled,
     }
 }
 
@@ -740,43 +3361,95 @@ fn status_style(status: &Status) -> Style {
         Status::Success(_) => Style::default().fg(Color::Green),
         Status::Skipped(_) => Style::default().fg(Color::Green),
         Status::Failed(_) => Style::default().fg(Color::Red),
+        Status::Cancelled(_) => Style::default().fg(Color::DarkGray),
     }
 }
 
-fn status_label(status: &Status) -> &'static str {
+fn status_label(status: &Status, kind: &ToolKind, operation: Operation) -> &'static str {
     match status {
         Status::Pending(_) => "pending",
+        Status::Success(_) if operation == Operation::Uninstall => "removed",
+        Status::Success(_) if operation == Operation::Upgrade => "upgraded",
+        Status::Success(_) if *kind == ToolKind::Tap => "tapped",
         Status::Success(_) => "installed",
         Status::Skipped(_) => "skipped",
         Status::Failed(_) => "failed",
+        Status::Cancelled(_) => "cancelled",
     }
 }
 
-fn perform_installations<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
-    app.clear_statuses();
-    let indices = app.selected_indices();
-    let total = indices.len();
-    for (position, &idx) in indices.iter().enumerate() {
-        let label = app.items[idx].tool.label.clone();
-        app.set_status(idx, Status::Pending("Installing...".to_string()));
-        app.progress = Some(format!("Installing {}/{}: {label}", position + 1, total));
-        terminal.draw(|frame| draw(frame, app))?;
+fn upgrade_tool_state(item: outdated::OutdatedItem, greedy: bool) -> ToolState {
+    let kind_label = match item.kind {
+        ToolKind::BrewCask => "cask",
+        ToolKind::Mas => "App Store",
+        _ => "brew formula",
+    };
+    ToolState {
+        tool: Tool {
+            label: format!(
+                "{} ({kind_label}) {} → {}",
+                item.name, item.current, item.available
+            ),
+            description: format!("Upgrade from {} to {}", item.current, item.available),
+            kind: item.kind,
+            name: item.name,
+            category: None,
+            fallbacks: Vec::new(),
+            app_id: item.app_id,
+            version: Some(item.available),
+            options: BrewOptions {
+                greedy,
+                ..BrewOptions::default()
+            },
+        },
+        selected: true,
+        status: None,
+        excluded: None,
+        origin: Origin::Installed,
+        log: Vec::new(),
+        duration: None,
+    }
+}
 
-        let result = install_tool(&app.items[idx].tool);
-        app.set_status(idx, result);
-        app.progress = Some(format!("Completed {}/{}: {label}", position + 1, total));
-        terminal.draw(|frame| draw(frame, app))?;
+/// State shared by every install in a single run, so expensive lookups
+/// such as listing VS Code extensions only happen once.
+#[derive(Default)]
+struct InstallContext {
+    vscode_extensions: Mutex<Option<HashSet<String>>>,
+}
+
+impl InstallContext {
+    /// Whether VS Code extension `id` is installed, listing the installed
+    /// extensions the first time it is asked.
+    fn has_vscode_extension(&self, code: &str, id: &str) -> Result<bool, String> {
+        let mut installed = self
+            .vscode_extensions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if installed.is_none() {
+            *installed = Some(list_vscode_extensions(code)?);
+        }
+        Ok(installed
+            .as_ref()
+            .is_some_and(|installed| installed.contains(&id.to_ascii_lowercase())))
     }
 
-    app.progress = Some("Installation complete. Press Enter or q to exit.".to_string());
-    terminal.draw(|frame| draw(frame, app))?;
-    Ok(())
+    fn add_vscode_extension(&self, id: &str) {
+        let mut installed = self
+            .vscode_extensions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(installed) = installed.as_mut() {
+            installed.insert(id.to_ascii_lowercase());
+        }
+    }
 }
 
-fn install_tool(tool: &Tool) -> Status {
+fn install_tool(tool: &Tool, context: &InstallContext) -> Status {
     match tool.kind {
-        ToolKind::BrewFormula => install_brew_formula(&tool.name),
-        ToolKind::BrewCask => install_brew_cask(&tool.name, &tool.fallbacks),
+        ToolKind::Tap => install_brew_tap(&tool.name, tool.options.tap_url.as_deref()),
+        ToolKind::BrewFormula => install_brew_formula(&tool.name, &tool.options),
+        ToolKind::BrewCask => install_brew_cask(&tool.name, &tool.fallbacks, &tool.options),
         ToolKind::Mas => {
             if let Some(app_id) = &tool.app_id {
                 install_mas_app(app_id, &tool.name)
@@ -784,32 +3457,169 @@ fn install_tool(tool: &Tool) -> Status {
                 Status::Failed("Missing MAS app id".to_string())
             }
         }
+        ToolKind::VsCode => install_vscode_extension(&tool.name, context),
+        ToolKind::Go => install_go_package(&tool.name, tool.version.as_deref()),
+    }
+}
+
+/// Removes a formula, cask or App Store app, as `brew bundle cleanup` does.
+fn uninstall_tool(tool: &Tool) -> Status {
+    let args: Vec<&str> = match (&tool.kind, &tool.app_id) {
+        (ToolKind::BrewFormula, _) | (ToolKind::BrewCask, _) if !brew_available() => {
+            return Status::Failed("Homebrew not available".to_string());
+        }
+        (ToolKind::BrewFormula, _) => vec!["brew", "uninstall", "--formula", &tool.name],
+        (ToolKind::BrewCask, _) => vec!["brew", "uninstall", "--cask", &tool.name],
+        (ToolKind::Mas, _) if !mas_available() => {
+            return Status::Failed("mas CLI not available".to_string());
+        }
+        (ToolKind::Mas, Some(app_id)) => vec!["mas", "uninstall", app_id],
+        (ToolKind::Mas, None) => return Status::Failed("Missing MAS app id".to_string()),
+        _ => return Status::Skipped("Removal is not supported for this entry".to_string()),
+    };
+
+    match run_command(&args) {
+        Ok(output) if output.status.success() => Status::Success(
+            shorten_message(&output.stdout).unwrap_or_else(|| format!("Uninstalled {}", tool.name)),
+        ),
+        Ok(output) => Status::Failed(output.failure_message()),
+        Err(err) => Status::Failed(format!("Failed to run {}: {err}", args[..2].join(" "))),
+    }
+}
+
+fn upgrade_tool(tool: &Tool) -> Status {
+    let mut args: Vec<&str> = match (&tool.kind, &tool.app_id) {
+        (ToolKind::BrewFormula, _) | (ToolKind::BrewCask, _) if !brew_available() => {
+            return Status::Failed("Homebrew not available".to_string());
+        }
+        (ToolKind::BrewFormula, _) => vec!["brew", "upgrade", "--formula", &tool.name],
+        (ToolKind::BrewCask, _) => vec!["brew", "upgrade", "--cask", &tool.name],
+        (ToolKind::Mas, _) if !mas_available() => {
+            return Status::Failed("mas CLI not available".to_string());
+        }
+        (ToolKind::Mas, Some(app_id)) => vec!["mas", "upgrade", app_id],
+        (ToolKind::Mas, None) => return Status::Failed("Missing MAS app id".to_string()),
+        _ => return Status::Skipped("Upgrade is not supported for this entry".to_string()),
+    };
+    if tool.kind == ToolKind::BrewCask && tool.options.greedy {
+        args.push("--greedy");
+    }
+
+    match run_command(&args) {
+        Ok(output) if output.status.success() => Status::Success(format!(
+            "Upgraded {} to {}",
+            tool.name,
+            tool.version.as_deref().unwrap_or("the latest version")
+        )),
+        Ok(output) => Status::Failed(output.failure_message()),
+        Err(err) => Status::Failed(format!("Failed to run {}: {err}", args[..2].join(" "))),
     }
 }
 
-fn install_brew_formula(name: &str) -> Status {
+fn install_brew_formula(name: &str, options: &BrewOptions) -> Status {
     if !brew_available() {
         return Status::Failed("Homebrew not available".to_string());
     }
 
-    if brew_list_installed("--formula", name) {
-        return Status::Skipped("Already installed".to_string());
+    let installed = if brew_list_installed("--formula", name) {
+        None
+    } else {
+        if let Err(err) = ensure_tap_for(name) {
+            return Status::Failed(err);
+        }
+
+        let mut args = vec!["brew", "install"];
+        args.extend(options.args.iter().map(String::as_str));
+        args.push(name);
+        match run_command(&args) {
+            Ok(output) if output.status.success() => {
+                Some(shorten_message(&output.stdout).unwrap_or_else(|| "Installed".to_string()))
+            }
+            Ok(output) => return Status::Failed(output.failure_message()),
+            Err(err) => return Status::Failed(format!("Failed to run brew: {err}")),
+        }
+    };
+
+    let notes = match apply_formula_options(name, options, installed.is_some()) {
+        Ok(notes) => notes,
+        Err(err) => return Status::Failed(err),
+    };
+    match installed {
+        Some(message) => Status::Success(with_notes(message, &notes)),
+        None => Status::Skipped(with_notes("Already installed".to_string(), &notes)),
     }
+}
 
-    match run_command(&["brew", "install", name]) {
-        Ok(output) if output.status.success() => Status::Success(
-            shorten_message(&output.stdout).unwrap_or_else(|| "Installed".to_string()),
-        ),
-        Ok(output) => Status::Failed(
-            shorten_message(&output.stderr)
-                .or_else(|| shorten_message(&output.stdout))
-                .unwrap_or_else(|| format!("Exit status {}", output.status_code())),
-        ),
-        Err(err) => Status::Failed(format!("Failed to run brew: {err}")),
+/// Runs the follow-up brew commands requested by a formula's options and
+/// returns short notes describing what was done. `changed` is true when the
+/// formula was installed during this run, for `restart_service: :changed`.
+/// An already installed formula is only linked or unlinked when its link
+/// state differs from the one requested.
+fn apply_formula_options(
+    name: &str,
+    options: &BrewOptions,
+    changed: bool,
+) -> Result<Vec<String>, String> {
+    let mut steps: Vec<(Vec<&str>, &str)> = Vec::new();
+    let link = options
+        .link
+        .filter(|&link| changed || brew_linked(name) != Some(link));
+    match link {
+        Some(false) => steps.push((vec!["brew", "unlink", name], "unlinked")),
+        Some(true) => steps.push((vec!["brew", "link", "--force", name], "linked")),
+        None => {}
+    }
+    let restart = match options.restart_service {
+        Some(ServiceRestart::Always) => true,
+        Some(ServiceRestart::Changed) => changed,
+        None => false,
+    };
+    if restart {
+        steps.push((
+            vec!["brew", "services", "restart", name],
+            "service restarted",
+        ));
+    } else if options.start_service && changed {
+        steps.push((vec!["brew", "services", "start", name], "service started"));
+    }
+
+    let mut notes = Vec::new();
+    for (args, note) in steps {
+        match run_command(&args) {
+            Ok(output) if output.status.success() => notes.push(note.to_string()),
+            Ok(output) => {
+                return Err(format!(
+                    "{} failed: {}",
+                    args.join(" "),
+                    output.failure_message()
+                ));
+            }
+            Err(err) => return Err(format!("Failed to run {}: {err}", args.join(" "))),
+        }
+    }
+    Ok(notes)
+}
+
+/// Whether a formula's keg is linked into the Homebrew prefix, or `None`
+/// when `brew info` cannot tell.
+fn brew_linked(name: &str) -> Option<bool> {
+    let output = run_command(&["brew", "info", "--json=v2", "--formula", name]).ok()?;
+    if !output.status.success() {
+        return None;
     }
+    let info: serde_json::Value = serde_json::from_str(&output.stdout).ok()?;
+    let linked_keg = info.pointer("/formulae/0/linked_keg")?;
+    Some(!linked_keg.is_null())
 }
 
-fn install_brew_cask(name: &str, fallbacks: &[String]) -> Status {
+fn with_notes(mut message: String, notes: &[String]) -> String {
+    if !notes.is_empty() {
+        message.push_str(&format!(" ({})", notes.join(", ")));
+    }
+    message
+}
+
+fn install_brew_cask(name: &str, fallbacks: &[String], options: &BrewOptions) -> Status {
     if !brew_available() {
         return Status::Failed("Homebrew not available".to_string());
     }
@@ -825,13 +3635,23 @@ fn install_brew_cask(name: &str, fallbacks: &[String]) -> Status {
             } else {
                 ""
             };
+            if options.greedy && brew_cask_outdated_greedy(candidate) {
+                return upgrade_brew_cask_greedy(candidate, suffix);
+            }
             return Status::Skipped(format!("Already installed{suffix}"));
         }
     }
 
+    if let Err(err) = ensure_tap_for(name) {
+        return Status::Failed(err);
+    }
+
     let mut last_error = None;
     for candidate in &candidates {
-        match run_command(&["brew", "install", "--cask", candidate]) {
+        let mut args = vec!["brew", "install", "--cask"];
+        args.extend(options.args.iter().map(String::as_str));
+        args.push(candidate);
+        match run_command(&args) {
             Ok(output) if output.status.success() => {
                 let mut message =
                     shorten_message(&output.stdout).unwrap_or_else(|| "Installed".to_string());
@@ -841,11 +3661,7 @@ fn install_brew_cask(name: &str, fallbacks: &[String]) -> Status {
                 return Status::Success(message);
             }
             Ok(output) => {
-                last_error = Some(
-                    shorten_message(&output.stderr)
-                        .or_else(|| shorten_message(&output.stdout))
-                        .unwrap_or_else(|| format!("Exit status {}", output.status_code())),
-                );
+                last_error = Some(output.failure_message());
             }
             Err(err) => {
                 last_error = Some(format!("Failed to run brew: {err}"));
@@ -856,6 +3672,74 @@ fn install_brew_cask(name: &str, fallbacks: &[String]) -> Status {
     Status::Failed(last_error.unwrap_or_else(|| "Install failed".to_string()))
 }
 
+fn upgrade_brew_cask_greedy(name: &str, suffix: &str) -> Status {
+    match run_command(&["brew", "upgrade", "--cask", "--greedy", name]) {
+        Ok(output) if output.status.success() => Status::Success(format!(
+            "{}{suffix}",
+            shorten_message(&output.stdout).unwrap_or_else(|| "Upgraded".to_string())
+        )),
+        Ok(output) => Status::Failed(output.failure_message()),
+        Err(err) => Status::Failed(format!("Failed to run brew: {err}")),
+    }
+}
+
+/// Whether an installed cask is outdated when auto-updating casks are
+/// included, matching `greedy: true` in the Brewfile.
+fn brew_cask_outdated_greedy(name: &str) -> bool {
+    match run_command(&["brew", "outdated", "--cask", "--greedy", "--quiet", name]) {
+        Ok(output) => output.stdout.lines().any(|line| !line.trim().is_empty()),
+        Err(_) => false,
+    }
+}
+
+fn install_brew_tap(name: &str, url: Option<&str>) -> Status {
+    if !brew_available() {
+        return Status::Failed("Homebrew not available".to_string());
+    }
+
+    if is_builtin_tap(name) {
+        return Status::Skipped("Built into Homebrew".to_string());
+    }
+
+    if brew_tapped(name) {
+        return Status::Skipped("Already tapped".to_string());
+    }
+
+    let mut args = vec!["brew", "tap", name];
+    args.extend(url);
+    match run_command(&args) {
+        Ok(output) if output.status.success() => Status::Success(
+            shorten_message(&output.stderr)
+                .or_else(|| shorten_message(&output.stdout))
+                .unwrap_or_else(|| "Tapped".to_string()),
+        ),
+        Ok(output) => Status::Failed(output.failure_message()),
+        Err(err) => Status::Failed(format!("Failed to run brew: {err}")),
+    }
+}
+
+/// Taps the repository a fully qualified formula or cask lives in, if needed.
+fn ensure_tap_for(name: &str) -> Result<(), String> {
+    let Some(tap) = qualified_tap(name) else {
+        return Ok(());
+    };
+    if is_builtin_tap(&tap) || brew_tapped(&tap) {
+        return Ok(());
+    }
+    match run_command(&["brew", "tap", &tap]) {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(format!("Failed to tap {tap}: {}", output.failure_message())),
+        Err(err) => Err(format!("Failed to run brew tap: {err}")),
+    }
+}
+
+fn is_builtin_tap(name: &str) -> bool {
+    matches!(
+        name.to_ascii_lowercase().as_str(),
+        "homebrew/core" | "homebrew/cask"
+    )
+}
+
 fn install_mas_app(app_id: &str, label: &str) -> Status {
     if !mas_available() {
         return Status::Failed("mas CLI not available".to_string());
@@ -881,12 +3765,137 @@ fn install_mas_app(app_id: &str, label: &str) -> Status {
         Ok(output) if output.status.success() => Status::Success(
             shorten_message(&output.stdout).unwrap_or_else(|| format!("Installed {label}")),
         ),
-        Ok(output) => Status::Failed(
+        Ok(output) => Status::Failed(output.failure_message()),
+        Err(err) => Status::Failed(format!("Failed to run mas install: {err}")),
+    }
+}
+
+fn install_vscode_extension(id: &str, context: &InstallContext) -> Status {
+    let Some(code) = code_command() else {
+        return Status::Failed("VS Code CLI (code) not available".to_string());
+    };
+
+    match context.has_vscode_extension(&code, id) {
+        Ok(true) => return Status::Skipped("Already installed".to_string()),
+        Ok(false) => {}
+        Err(err) => return Status::Failed(err),
+    }
+
+    match run_command(&[&code, "--install-extension", id]) {
+        Ok(output) if output.status.success() => {
+            context.add_vscode_extension(id);
+            Status::Success(
+                shorten_message(&output.stdout).unwrap_or_else(|| "Installed".to_string()),
+            )
+        }
+        Ok(output) => Status::Failed(output.failure_message()),
+        Err(err) => Status::Failed(format!("Failed to run code: {err}")),
+    }
+}
+
+fn install_go_package(path: &str, version: Option<&str>) -> Status {
+    if !go_available() {
+        return Status::Failed("Go toolchain not available".to_string());
+    }
+
+    // An unpinned or `latest` entry keeps whatever binary is there; a pinned
+    // one is reinstalled unless the binary was built from that version.
+    let binary = go_binary_name(path);
+    if let Some(dir) = go_bin_dir()
+        && dir.join(&binary).is_file()
+    {
+        match version.filter(|version| *version != "latest") {
+            None => return Status::Skipped(format!("Already installed in {}", dir.display())),
+            Some(version) if go_binary_version(&dir.join(&binary)).as_deref() == Some(version) => {
+                return Status::Skipped(format!(
+                    "{version} already installed in {}",
+                    dir.display()
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+
+    let target = format!("{path}@{}", version.unwrap_or("latest"));
+    match run_command(&["go", "install", &target]) {
+        Ok(output) if output.status.success() => Status::Success(
             shorten_message(&output.stderr)
                 .or_else(|| shorten_message(&output.stdout))
-                .unwrap_or_else(|| format!("Exit status {}", output.status_code())),
+                .unwrap_or_else(|| format!("Installed {binary}")),
         ),
-        Err(err) => Status::Failed(format!("Failed to run mas install: {err}")),
+        Ok(output) => Status::Failed(output.failure_message()),
+        Err(err) => Status::Failed(format!("Failed to run go install: {err}")),
+    }
+}
+
+/// The module version a Go binary was built from, per `go version -m`.
+fn go_binary_version(binary: &Path) -> Option<String> {
+    let binary = binary.to_str()?;
+    let output = run_command(&["go", "version", "-m", binary]).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_go_module_version(&output.stdout)
+}
+
+/// Reads the version from the `mod` line of `go version -m` output:
+/// `\tmod\t<module>\t<version>\t<checksum>`.
+fn parse_go_module_version(text: &str) -> Option<String> {
+    text.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        if fields.next()? != "mod" {
+            return None;
+        }
+        fields.nth(1).map(str::to_string)
+    })
+}
+
+/// Directory `go install` writes binaries to: `$GOBIN`, else the first
+/// `$GOPATH` entry's `bin`, else Go's default of `~/go/bin`.
+fn go_bin_dir() -> Option<PathBuf> {
+    if let Ok(gobin) = std::env::var("GOBIN")
+        && !gobin.trim().is_empty()
+    {
+        return Some(PathBuf::from(gobin.trim()));
+    }
+    if let Ok(gopath) = std::env::var("GOPATH")
+        && let Some(first) = std::env::split_paths(&gopath).next()
+        && !first.as_os_str().is_empty()
+    {
+        return Some(first.join("bin"));
+    }
+    std::env::var("HOME")
+        .ok()
+        .map(|home| Path::new(&home).join("go").join("bin"))
+}
+
+fn list_vscode_extensions(code: &str) -> Result<HashSet<String>, String> {
+    match run_command(&[code, "--list-extensions"]) {
+        Ok(output) if output.status.success() => Ok(output
+            .stdout
+            .lines()
+            .map(|line| line.trim().to_ascii_lowercase())
+            .filter(|line| !line.is_empty())
+            .collect()),
+        Ok(output) => Err(format!(
+            "code --list-extensions failed: {}",
+            output.failure_message()
+        )),
+        Err(err) => Err(format!("Failed to run code --list-extensions: {err}")),
+    }
+}
+
+/// Locates the `code` CLI, falling back to the copy bundled inside the app
+/// when the shell command has not been installed onto the PATH.
+fn code_command() -> Option<String> {
+    if let Ok(path) = which("code") {
+        return Some(path.display().to_string());
+    }
+    let bundled = Path::new(VSCODE_BUNDLED_CLI);
+    if bundled.is_file() {
+        Some(bundled.display().to_string())
+    } else {
+        None
     }
 }
 
@@ -894,10 +3903,24 @@ fn brew_available() -> bool {
     which("brew").is_ok()
 }
 
+fn go_available() -> bool {
+    which("go").is_ok()
+}
+
 fn mas_available() -> bool {
     which("mas").is_ok()
 }
 
+fn brew_tapped(name: &str) -> bool {
+    match run_command(&["brew", "tap"]) {
+        Ok(output) if output.status.success() => output
+            .stdout
+            .lines()
+            .any(|line| line.trim().eq_ignore_ascii_case(name)),
+        _ => false,
+    }
+}
+
 fn brew_list_installed(flag: &str, name: &str) -> bool {
     match run_command(&["brew", "list", flag, "--versions", name]) {
         Ok(output) => output.status.success(),
@@ -915,6 +3938,12 @@ impl CommandOutput {
     fn status_code(&self) -> i32 {
         self.status.code().unwrap_or(-1)
     }
+
+    fn failure_message(&self) -> String {
+        shorten_message(&self.stderr)
+            .or_else(|| shorten_message(&self.stdout))
+            .unwrap_or_else(|| format!("Exit status {}", self.status_code()))
+    }
 }
 
 fn run_command(args: &[&str]) -> io::Result<CommandOutput> {
@@ -922,9 +3951,7 @@ fn run_command(args: &[&str]) -> io::Result<CommandOutput> {
     if args.len() > 1 {
         command.args(&args[1..]);
     }
-    command.stdout(std::process::Stdio::piped());
-    command.stderr(std::process::Stdio::piped());
-    let output = command.output()?;
+    let output = process::output(&mut command)?;
     Ok(CommandOutput {
         status: output.status,
         stdout: String::from_utf8_lossy(&output.stdout).to_string(),
@@ -938,11 +3965,58 @@ fn shorten_message(output: &str) -> Option<String> {
         return None;
     }
     const MAX_LEN: usize = 80;
-    if line.len() <= MAX_LEN {
-        Some(line.to_string())
-    } else {
-        let mut shortened = line[..MAX_LEN.min(line.len())].trim_end().to_string();
-        shortened.push('…');
-        Some(shortened)
+    match line.char_indices().nth(MAX_LEN) {
+        None => Some(line.to_string()),
+        Some((end, _)) => {
+            let mut shortened = line[..end].trim_end().to_string();
+            shortened.push('…');
+            Some(shortened)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_go_versions() {
+        assert_eq!(
+            split_go_version("golang.org/x/tools/gopls@v0.15.0"),
+            (
+                "golang.org/x/tools/gopls".to_string(),
+                Some("v0.15.0".to_string())
+            )
+        );
+        assert_eq!(
+            split_go_version("github.com/a/b@"),
+            ("github.com/a/b".to_string(), None)
+        );
+        assert_eq!(
+            split_go_version("github.com/a/b"),
+            ("github.com/a/b".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn names_go_binaries() {
+        assert_eq!(go_binary_name("golang.org/x/tools/gopls"), "gopls");
+        assert_eq!(go_binary_name("github.com/a/tool/v2"), "tool");
+        assert_eq!(go_binary_name("github.com/a/tool/v2/"), "tool");
+        assert_eq!(go_binary_name("github.com/a/tool/"), "tool");
+        assert_eq!(go_binary_name("github.com/a/vim"), "vim");
+        assert_eq!(go_binary_name("github.com/a/v2x"), "v2x");
+        assert_eq!(go_binary_name("v2"), "v2");
+    }
+
+    #[test]
+    fn reads_the_module_version_of_a_go_binary() {
+        let text = "/home/me/go/bin/gopls: go1.22.0
+\tpath\tgolang.org/x/tools/gopls
+\tmod\tgolang.org/x/tools/gopls\tv0.15.0\th1:abc=
+\tdep\tgolang.org/x/mod\tv0.15.0\th1:def=
+";
+        assert_eq!(parse_go_module_version(text).as_deref(), Some("v0.15.0"));
+        assert_eq!(parse_go_module_version("gopls: go1.22.0\n"), None);
     }
 }
@@ -0,0 +1,236 @@
+//! Installed software with newer versions available, from
+//! `brew outdated --json=v2` and `mas outdated`.
+
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use serde_json::Value;
+
+use crate::inventory::command_lines;
+use crate::{ToolKind, brew_available, mas_available, run_command};
+
+#[derive(Clone, Debug)]
+pub struct OutdatedItem {
+    pub kind: ToolKind,
+    pub name: String,
+    /// App Store id for `mas` apps.
+    pub app_id: Option<String>,
+    pub current: String,
+    pub available: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Outdated {
+    pub items: Vec<OutdatedItem>,
+    /// Pinned formulae, which `brew upgrade` refuses to touch.
+    pub pinned: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Collects on a background thread; the channel yields the result once.
+pub fn spawn(greedy: bool) -> Receiver<Outdated> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(Outdated::collect(greedy));
+    });
+    receiver
+}
+
+impl Outdated {
+    /// Queries Homebrew and the App Store. With `greedy`, casks that update
+    /// themselves (`auto_updates true` or `version :latest`) are included.
+    pub fn collect(greedy: bool) -> Self {
+        let mut outdated = Outdated::default();
+
+        if brew_available() {
+            let mut args = vec!["brew", "outdated", "--json=v2"];
+            if greedy {
+                args.push("--greedy");
+            }
+            match run_command(&args) {
+                Ok(output) if output.status.success() => {
+                    if let Err(err) = outdated.add_brew_json(&output.stdout) {
+                        outdated.warnings.push(err);
+                    }
+                }
+                Ok(output) => outdated.warnings.push(format!(
+                    "{} failed: {}",
+                    args.join(" "),
+                    output.failure_message()
+                )),
+                Err(err) => outdated
+                    .warnings
+                    .push(format!("Failed to run {}: {err}", args.join(" "))),
+            }
+        } else {
+            outdated
+                .warnings
+                .push("Homebrew not available; skipped formulae and casks".to_string());
+        }
+
+        if mas_available() {
+            match command_lines(&["mas", "outdated"]) {
+                Ok(lines) => outdated
+                    .items
+                    .extend(lines.iter().filter_map(|line| parse_mas_outdated(line))),
+                Err(err) => outdated.warnings.push(err),
+            }
+        } else {
+            outdated
+                .warnings
+                .push("mas CLI not available; skipped App Store apps".to_string());
+        }
+
+        outdated
+    }
+
+    fn add_brew_json(&mut self, json: &str) -> Result<(), String> {
+        let value: Value = serde_json::from_str(json)
+            .map_err(|err| format!("Could not parse brew outdated output: {err}"))?;
+        for (key, kind) in [
+            ("formulae", ToolKind::BrewFormula),
+            ("casks", ToolKind::BrewCask),
+        ] {
+            let entries = value.get(key).and_then(Value::as_array);
+            for entry in entries.into_iter().flatten() {
+                let Some(name) = entry.get("name").and_then(Value::as_str) else {
+                    continue;
+                };
+                if entry.get("pinned").and_then(Value::as_bool) == Some(true) {
+                    self.pinned.push(name.to_string());
+                    continue;
+                }
+                self.items.push(OutdatedItem {
+                    kind: kind.clone(),
+                    name: name.to_string(),
+                    app_id: None,
+                    current: installed_version(entry.get("installed_versions")),
+                    available: entry
+                        .get("current_version")
+                        .and_then(Value::as_str)
+                        .unwrap_or("?")
+                        .to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Formulae report every installed version as an array; older Homebrew
+/// releases report casks as a single string.
+fn installed_version(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(version)) => version.clone(),
+        Some(Value::Array(versions)) => versions
+            .iter()
+            .filter_map(Value::as_str)
+            .next_back()
+            .unwrap_or("?")
+            .to_string(),
+        _ => "?".to_string(),
+    }
+}
+
+/// Parses a `mas outdated` line such as `497799835 Xcode (15.0 -> 15.1)`.
+fn parse_mas_outdated(line: &str) -> Option<OutdatedItem> {
+    let line = line.trim();
+    let (id, rest) = line.split_once(char::is_whitespace)?;
+    if id.is_empty() || !id.chars().all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    let rest = rest.trim();
+    let (name, versions) = match rest.rfind(" (") {
+        Some(index) if rest.ends_with(')') => (&rest[..index], &rest[index + 2..rest.len() - 1]),
+        _ => (rest, ""),
+    };
+    let (current, available) = versions.split_once("->").unwrap_or(("?", "?"));
+    Some(OutdatedItem {
+        kind: ToolKind::Mas,
+        name: name.trim().to_string(),
+        app_id: Some(id.to_string()),
+        current: current.trim().to_string(),
+        available: available.trim().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_brew_outdated_json() {
+        let json = r#"{
+          "formulae": [
+            {"name": "jq", "installed_versions": ["1.6", "1.7"], "current_version": "1.7.1", "pinned": false},
+            {"name": "node@18", "installed_versions": ["18.1.0"], "current_version": "18.2.0", "pinned": true},
+            {"installed_versions": ["1.0"], "current_version": "2.0"}
+          ],
+          "casks": [
+            {"name": "firefox", "installed_versions": "120.0", "current_version": "121.0"},
+            {"name": "zed", "installed_versions": ["0.1"]}
+          ]
+        }"#;
+        let mut outdated = Outdated::default();
+        outdated.add_brew_json(json).unwrap();
+
+        let items: Vec<(ToolKind, &str, &str, &str)> = outdated
+            .items
+            .iter()
+            .map(|item| {
+                (
+                    item.kind.clone(),
+                    item.name.as_str(),
+                    item.current.as_str(),
+                    item.available.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            items,
+            [
+                (ToolKind::BrewFormula, "jq", "1.7", "1.7.1"),
+                (ToolKind::BrewCask, "firefox", "120.0", "121.0"),
+                (ToolKind::BrewCask, "zed", "0.1", "?"),
+            ]
+        );
+        assert_eq!(outdated.pinned, ["node@18"]);
+    }
+
+    #[test]
+    fn reports_unparsable_brew_output() {
+        let mut outdated = Outdated::default();
+        let err = outdated.add_brew_json("Error: not json").unwrap_err();
+        assert!(err.starts_with("Could not parse brew outdated output"));
+        assert!(outdated.items.is_empty());
+    }
+
+    #[test]
+    fn reads_mas_outdated_lines() {
+        let item = parse_mas_outdated("497799835 Xcode (15.0 -> 15.1)").unwrap();
+        assert_eq!(item.kind, ToolKind::Mas);
+        assert_eq!(item.app_id.as_deref(), Some("497799835"));
+        assert_eq!(
+            (
+                item.name.as_str(),
+                item.current.as_str(),
+                item.available.as_str()
+            ),
+            ("Xcode", "15.0", "15.1")
+        );
+
+        let item = parse_mas_outdated("  409183694   Keynote (Apple) (13.1 -> 14.0)").unwrap();
+        assert_eq!(item.name, "Keynote (Apple)");
+        assert_eq!(
+            (item.current.as_str(), item.available.as_str()),
+            ("13.1", "14.0")
+        );
+
+        let item = parse_mas_outdated("1295203466 Microsoft Remote Desktop").unwrap();
+        assert_eq!(item.name, "Microsoft Remote Desktop");
+        assert_eq!((item.current.as_str(), item.available.as_str()), ("?", "?"));
+
+        assert!(parse_mas_outdated("Warning: not signed in").is_none());
+        assert!(parse_mas_outdated("").is_none());
+    }
+}
@@ -0,0 +1,168 @@
+//! Runs the selected operation on a bounded pool of worker threads.
+//!
+//! Homebrew holds a global lock while it installs, so every tap, formula
+//! and cask job goes through a single lane in order (taps first), one
+//! command per item so each gets its own log and timing. App Store, VS
+//! Code and Go jobs are shared among the remaining workers and run
+//! concurrently with the Homebrew lane.
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::process::{self, CancelToken, LogEvent, LogSink};
+use crate::{
+    InstallContext, Operation, Status, Tool, ToolKind, install_tool, uninstall_tool, upgrade_tool,
+};
+
+/// Environment variable overriding the number of worker threads.
+pub const JOBS_ENV: &str = "INSTALL_TOOLS_JOBS";
+
+const DEFAULT_MAX_JOBS: usize = 4;
+
+#[derive(Debug)]
+pub enum JobEvent {
+    /// The job for this item index has started.
+    Started(usize),
+    /// Output from a command run for this item index.
+    Log(usize, LogEvent),
+    Finished(usize, Status),
+}
+
+/// Number of workers from `INSTALL_TOOLS_JOBS`, or the number of CPUs
+/// capped at four.
+pub fn worker_count() -> usize {
+    std::env::var(JOBS_ENV)
+        .ok()
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|&jobs| jobs > 0)
+        .unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map_or(1, |count| count.get())
+                .min(DEFAULT_MAX_JOBS)
+        })
+}
+
+/// Starts `jobs` (item index and tool) on `workers` threads and returns the
+/// channel their progress is reported on. The channel disconnects once
+/// every job has finished. Cancelling `cancel` kills the run's commands and
+/// reports the jobs not yet finished as cancelled.
+pub fn spawn(
+    jobs: Vec<(usize, Tool)>,
+    operation: Operation,
+    workers: usize,
+    cancel: Option<Arc<CancelToken>>,
+) -> Receiver<JobEvent> {
+    let (brew, mut other): (Vec<_>, Vec<_>) = jobs.into_iter().partition(|(_, tool)| {
+        matches!(
+            tool.kind,
+            ToolKind::Tap | ToolKind::BrewFormula | ToolKind::BrewCask
+        )
+    });
+    // Keep the original order otherwise; only taps need to come first.
+    let mut brew = brew;
+    brew.sort_by_key(|(_, tool)| tool.kind != ToolKind::Tap);
+    other.sort_by_key(|(index, _)| *index);
+
+    let shared = Arc::new(Mutex::new(VecDeque::from(other)));
+    let context = Arc::new(InstallContext::default());
+    let (sender, receiver) = mpsc::channel();
+
+    {
+        let shared = Arc::clone(&shared);
+        let context = Arc::clone(&context);
+        let cancel = cancel.clone();
+        let sender = sender.clone();
+        thread::spawn(move || {
+            process::set_cancel_token(cancel);
+            run_brew_lane(brew, operation, &context, &sender);
+            // With a single worker the Homebrew lane drains the rest too.
+            run_shared(&shared, operation, &context, &sender);
+        });
+    }
+    for _ in 1..workers.max(1) {
+        let shared = Arc::clone(&shared);
+        let context = Arc::clone(&context);
+        let cancel = cancel.clone();
+        let sender = sender.clone();
+        thread::spawn(move || {
+            process::set_cancel_token(cancel);
+            run_shared(&shared, operation, &context, &sender);
+        });
+    }
+    receiver
+}
+
+fn run_job(
+    index: usize,
+    tool: &Tool,
+    operation: Operation,
+    context: &InstallContext,
+    sender: &Sender<JobEvent>,
+) {
+    if process::cancelled() {
+        let _ = sender.send(JobEvent::Finished(index, not_started()));
+        return;
+    }
+    let _ = sender.send(JobEvent::Started(index));
+    process::set_log_sink(Some(log_sink(sender, index)));
+    let status = match operation {
+        Operation::Install => install_tool(tool, context),
+        Operation::Uninstall => uninstall_tool(tool),
+        Operation::Upgrade => upgrade_tool(tool),
+    };
+    process::set_log_sink(None);
+    let _ = sender.send(JobEvent::Finished(index, interrupted(status)));
+}
+
+/// Forwards command output to the log of the item at `index`.
+fn log_sink(sender: &Sender<JobEvent>, index: usize) -> LogSink {
+    let sender = sender.clone();
+    Arc::new(move |event: LogEvent| {
+        let _ = sender.send(JobEvent::Log(index, event));
+    })
+}
+
+fn not_started() -> Status {
+    Status::Cancelled("Cancelled before it started".to_string())
+}
+
+/// A command killed by cancelling the run surfaces as a failure.
+fn interrupted(status: Status) -> Status {
+    match status {
+        Status::Failed(_) if process::cancelled() => {
+            Status::Cancelled("Cancelled while running".to_string())
+        }
+        other => other,
+    }
+}
+
+fn run_shared(
+    shared: &Mutex<VecDeque<(usize, Tool)>>,
+    operation: Operation,
+    context: &InstallContext,
+    sender: &Sender<JobEvent>,
+) {
+    loop {
+        let job = match shared.lock() {
+            Ok(mut queue) => queue.pop_front(),
+            Err(_) => None,
+        };
+        let Some((index, tool)) = job else {
+            return;
+        };
+        run_job(index, &tool, operation, context, sender);
+    }
+}
+
+fn run_brew_lane(
+    jobs: Vec<(usize, Tool)>,
+    operation: Operation,
+    context: &InstallContext,
+    sender: &Sender<JobEvent>,
+) {
+    for (index, tool) in jobs {
+        run_job(index, &tool, operation, context, sender);
+    }
+}
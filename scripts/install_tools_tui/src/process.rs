@@ -0,0 +1,261 @@
+//! Child processes started by installs, tracked so a run can be cancelled
+//! and streamed line by line to whoever is watching the current job.
+
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Cancels a single run: kills the commands it has running and makes its
+/// later commands fail immediately. Commands run outside the run, such as
+/// the installed-state probe, are unaffected.
+#[derive(Debug, Default)]
+pub struct CancelToken {
+    cancelled: AtomicBool,
+    running: Mutex<Vec<Arc<Mutex<Child>>>>,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        if let Ok(running) = self.running.lock() {
+            for child in running.iter() {
+                if let Ok(mut child) = child.lock() {
+                    kill_group(&mut child);
+                }
+            }
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// One step of a command's life, as shown in the results log pane.
+#[derive(Clone, Debug)]
+pub enum LogEvent {
+    /// The command line about to run.
+    Command(String),
+    Output {
+        line: String,
+        stderr: bool,
+    },
+    Exit {
+        code: Option<i32>,
+        duration: Duration,
+    },
+}
+
+/// Receives the log of every command run on the current thread.
+pub type LogSink = Arc<dyn Fn(LogEvent) + Send + Sync>;
+
+thread_local! {
+    static SINK: RefCell<Option<LogSink>> = const { RefCell::new(None) };
+    static TOKEN: RefCell<Option<Arc<CancelToken>>> = const { RefCell::new(None) };
+}
+
+/// Routes the output of commands run on this thread to `sink`, or stops
+/// streaming with `None`.
+pub fn set_log_sink(sink: Option<LogSink>) {
+    SINK.with(|current| *current.borrow_mut() = sink);
+}
+
+fn emit(sink: &Option<LogSink>, event: LogEvent) {
+    if let Some(sink) = sink {
+        sink(event);
+    }
+}
+
+/// Makes commands run on this thread part of the run `token` cancels, or
+/// leaves them uncancellable with `None`.
+pub fn set_cancel_token(token: Option<Arc<CancelToken>>) {
+    TOKEN.with(|current| *current.borrow_mut() = token);
+}
+
+/// Whether the run this thread works for has been cancelled.
+pub fn cancelled() -> bool {
+    TOKEN.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+    })
+}
+
+/// Set from the signal handler, which may do little more than this.
+static SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn note_signal(_: libc::c_int) {
+    SIGNALLED.store(true, Ordering::SeqCst);
+}
+
+/// Cancels `token` instead of dying when the process gets SIGINT or
+/// SIGTERM, so an interrupted run still ends normally and reports what
+/// it got through.
+pub fn cancel_on_signals(token: Arc<CancelToken>) {
+    let handler = note_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only stores to an atomic, which is
+    // async-signal-safe.
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+    thread::spawn(move || {
+        while !SIGNALLED.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(50));
+        }
+        token.cancel();
+    });
+}
+
+/// Like `Command::output`, but when the thread has a cancel token the child
+/// runs in its own process group, which the token kills as a whole. Returns
+/// an `Interrupted` error once the run is cancelled.
+pub fn output(command: &mut Command) -> io::Result<Output> {
+    let token = TOKEN.with(|current| current.borrow().clone());
+    let cancelled = || token.as_ref().is_some_and(|token| token.is_cancelled());
+    if cancelled() {
+        return Err(interrupted());
+    }
+    let sink = SINK.with(|current| current.borrow().clone());
+    emit(&sink, LogEvent::Command(command_line(command)));
+    let started = Instant::now();
+    if token.is_some() {
+        command.process_group(0);
+    }
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = read_in_background(child.stdout.take(), sink.clone(), false);
+    let stderr = read_in_background(child.stderr.take(), sink.clone(), true);
+
+    let child = Arc::new(Mutex::new(child));
+    if let Some(token) = &token
+        && let Ok(mut running) = token.running.lock()
+    {
+        running.push(Arc::clone(&child));
+    }
+    // `cancel` may have run between the check above and registering.
+    if cancelled()
+        && let Ok(mut child) = child.lock()
+    {
+        kill_group(&mut child);
+    }
+    let status = wait(&child);
+    if let Some(token) = &token
+        && let Ok(mut running) = token.running.lock()
+    {
+        running.retain(|other| !Arc::ptr_eq(other, &child));
+    }
+
+    let status = status?;
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    emit(
+        &sink,
+        LogEvent::Exit {
+            code: status.code(),
+            duration: started.elapsed(),
+        },
+    );
+    if cancelled() && !status.success() {
+        return Err(interrupted());
+    }
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+/// The program and its arguments, quoted where needed, for the log.
+fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("'{arg}'")
+            } else {
+                arg.into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Kills `child` and everything it started. Only children spawned under a
+/// cancel token lead their own process group.
+fn kill_group(child: &mut Child) {
+    // Once reaped, the child's id may already belong to another process.
+    if !matches!(child.try_wait(), Ok(None)) {
+        return;
+    }
+    if let Ok(pid) = i32::try_from(child.id()) {
+        // SAFETY: `kill` has no memory-safety preconditions.
+        unsafe {
+            libc::kill(-pid, libc::SIGKILL);
+        }
+    }
+}
+
+/// Polls instead of blocking in `wait` so `cancel` can take the lock.
+fn wait(child: &Mutex<Child>) -> io::Result<std::process::ExitStatus> {
+    loop {
+        let status = match child.lock() {
+            Ok(mut child) => child.try_wait()?,
+            Err(_) => return Err(io::Error::other("child process lock poisoned")),
+        };
+        if let Some(status) = status {
+            return Ok(status);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Collects everything the pipe produces, passing each line to `sink` as
+/// it arrives. Progress bars redraw with `\r`, so only the text after the
+/// last one is streamed.
+fn read_in_background<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    sink: Option<LogSink>,
+    stderr: bool,
+) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let Some(pipe) = pipe else {
+            return buffer;
+        };
+        let mut reader = BufReader::new(pipe);
+        loop {
+            let start = buffer.len();
+            match reader.read_until(b'\n', &mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let text = String::from_utf8_lossy(&buffer[start..]);
+                    let line = text.trim_end_matches(['\n', '\r']);
+                    let line = line.rsplit('\r').next().unwrap_or(line);
+                    emit(
+                        &sink,
+                        LogEvent::Output {
+                            line: line.to_string(),
+                            stderr,
+                        },
+                    );
+                }
+            }
+        }
+        buffer
+    })
+}
+
+fn interrupted() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "cancelled")
+}
@@ -0,0 +1,282 @@
+//! Structured reports of a finished run, in JSON and JUnit XML.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde_json::{Value, json};
+
+use crate::cli::Options;
+use crate::history::{self, item_json};
+use crate::{Operation, Status, ToolState, log_entries, run_command};
+
+type Writer = fn(&Path, Operation, SystemTime, &[ToolState]) -> Result<(), String>;
+
+/// Writes the reports requested by `options` and returns a note on each.
+pub fn write_requested(
+    options: &Options,
+    operation: Operation,
+    started: SystemTime,
+    items: &[ToolState],
+) -> Vec<String> {
+    let writers: [(&Option<PathBuf>, Writer); 2] = [
+        (&options.report_json, write_json),
+        (&options.report_junit, write_junit),
+    ];
+    writers
+        .into_iter()
+        .filter_map(|(path, write)| {
+            let path = path.as_ref()?;
+            Some(match write(path, operation, started, items) {
+                Ok(()) => format!("Report written to {}.", path.display()),
+                Err(err) => err,
+            })
+        })
+        .collect()
+}
+
+/// Whether any item failed or was cancelled, which makes the run exit with
+/// a non-zero status.
+pub fn any_failed(items: &[ToolState]) -> bool {
+    items
+        .iter()
+        .any(|item| matches!(item.status, Some(Status::Failed(_) | Status::Cancelled(_))))
+}
+
+/// Writes a JSON report of the items that took part in the run.
+pub fn write_json(
+    path: &Path,
+    operation: Operation,
+    started: SystemTime,
+    items: &[ToolState],
+) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(&json_report(operation, started, items))
+        .map_err(|err| format!("Failed to encode report: {err}"))?;
+    fs::write(path, text).map_err(|err| format!("Failed to write {}: {err}", path.display()))
+}
+
+/// The JSON report: counts by outcome and a record per item that ran.
+pub fn json_report(operation: Operation, started: SystemTime, items: &[ToolState]) -> Value {
+    let ran: Vec<&ToolState> = items.iter().filter(|item| item.status.is_some()).collect();
+    let count = |name: &str| {
+        ran.iter()
+            .filter(|item| item.status.as_ref().map(history::status_name) == Some(name))
+            .count()
+    };
+    json!({
+        "hostname": hostname(),
+        "operation": operation.name(),
+        "started": iso_timestamp(started),
+        "summary": {
+            "total": ran.len(),
+            "success": count("success"),
+            "skipped": count("skipped"),
+            "failed": count("failed"),
+            "cancelled": count("cancelled"),
+        },
+        "items": ran.iter().map(|item| item_json(item)).collect::<Vec<_>>(),
+    })
+}
+
+/// Writes a JUnit XML report with one test case per item. Items that were
+/// already installed pass; cancelled items are reported as skipped.
+pub fn write_junit(
+    path: &Path,
+    operation: Operation,
+    started: SystemTime,
+    items: &[ToolState],
+) -> Result<(), String> {
+    let ran: Vec<&ToolState> = items.iter().filter(|item| item.status.is_some()).collect();
+    let failures = ran
+        .iter()
+        .filter(|item| matches!(item.status, Some(Status::Failed(_))))
+        .count();
+    let skipped = ran
+        .iter()
+        .filter(|item| matches!(item.status, Some(Status::Cancelled(_))))
+        .count();
+    let total: Duration = ran.iter().filter_map(|item| item.duration).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"install_tools_tui\" tests=\"{}\" failures=\"{failures}\" skipped=\"{skipped}\" time=\"{:.3}\">\n",
+        ran.len(),
+        total.as_secs_f64()
+    ));
+    xml.push_str(&format!(
+        "  <testsuite name=\"{}\" hostname=\"{}\" timestamp=\"{}\" tests=\"{}\" failures=\"{failures}\" skipped=\"{skipped}\" time=\"{:.3}\">\n",
+        operation.name(),
+        escape(&hostname()),
+        iso_timestamp(started),
+        ran.len(),
+        total.as_secs_f64()
+    ));
+    for item in ran {
+        let Some(status) = &item.status else {
+            continue;
+        };
+        xml.push_str(&format!(
+            "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\">\n",
+            item.tool.kind.entry_kind().keyword(),
+            escape(&item.tool.name),
+            item.duration.unwrap_or_default().as_secs_f64()
+        ));
+        let message = escape(status.message());
+        let log = log_entries(&item.log)
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect::<Vec<_>>()
+            .join("\n");
+        match status {
+            Status::Failed(_) => xml.push_str(&format!(
+                "      <failure message=\"{message}\">{}</failure>\n",
+                escape(&log)
+            )),
+            Status::Cancelled(_) => {
+                xml.push_str(&format!("      <skipped message=\"{message}\"/>\n"));
+            }
+            // Reports are written once every started item has finished.
+            Status::Success(_) | Status::Skipped(_) | Status::Pending(_) => {}
+        }
+        if !log.is_empty() && !matches!(status, Status::Failed(_)) {
+            xml.push_str(&format!(
+                "      <system-out>{}</system-out>\n",
+                escape(&log)
+            ));
+        }
+        xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    fs::write(path, xml).map_err(|err| format!("Failed to write {}: {err}", path.display()))
+}
+
+/// `history::timestamp` with colons in the time, as ISO 8601 readers expect.
+fn iso_timestamp(time: SystemTime) -> String {
+    let stamp = history::timestamp(time);
+    match stamp.split_once('T') {
+        Some((date, clock)) => format!("{date}T{}", clock.replace('-', ":")),
+        None => stamp,
+    }
+}
+
+fn hostname() -> String {
+    run_command(&["hostname"])
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| output.stdout.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Escapes text for XML attributes and content, dropping control
+/// characters XML 1.0 does not allow.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' | '\t' | '\r' => escaped.push(ch),
+            ch if ch.is_control() => {}
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LogEvent, ToolKind, tap_tool_state};
+
+    fn item(name: &str, status: Option<Status>) -> ToolState {
+        let mut item = tap_tool_state(name, String::new());
+        item.tool.kind = ToolKind::BrewFormula;
+        item.status = status;
+        item
+    }
+
+    fn items() -> Vec<ToolState> {
+        let mut failed = item("b&d", Some(Status::Failed("exit <1>".to_string())));
+        failed.log = vec![
+            LogEvent::Command("brew install b&d".to_string()),
+            LogEvent::Output {
+                line: "Error: \"b&d\" isn't\u{1b}[0m here".to_string(),
+                stderr: true,
+            },
+        ];
+        failed.duration = Some(Duration::from_millis(1500));
+        vec![
+            item("jq", Some(Status::Success("Installed".to_string()))),
+            item("rg", Some(Status::Skipped("Already installed".to_string()))),
+            failed,
+            item("fd", Some(Status::Cancelled("Cancelled".to_string()))),
+            item("unselected", None),
+        ]
+    }
+
+    #[test]
+    fn escapes_markup_and_drops_control_characters() {
+        assert_eq!(
+            escape("a & <b> \"c\" 'd'"),
+            "a &amp; &lt;b&gt; &quot;c&quot; &apos;d&apos;"
+        );
+        assert_eq!(escape("x\u{1b}[0m\u{0}y\tz\n"), "x[0my\tz\n");
+    }
+
+    #[test]
+    fn formats_iso_timestamps() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(951_827_696);
+        assert_eq!(iso_timestamp(time), "2000-02-29T12:34:56Z");
+        assert_eq!(
+            iso_timestamp(SystemTime::UNIX_EPOCH),
+            "1970-01-01T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn json_summary_counts_only_items_that_ran() {
+        let report = json_report(Operation::Install, SystemTime::UNIX_EPOCH, &items());
+        assert_eq!(report["operation"], "install");
+        assert_eq!(report["started"], "1970-01-01T00:00:00Z");
+        assert_eq!(
+            report["summary"],
+            json!({"total": 4, "success": 1, "skipped": 1, "failed": 1, "cancelled": 1})
+        );
+        let tools: Vec<&str> = report["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["tool"].as_str().unwrap())
+            .collect();
+        assert_eq!(tools, ["jq", "rg", "b&d", "fd"]);
+        assert_eq!(report["items"][2]["command"], "brew install b&d");
+    }
+
+    #[test]
+    fn junit_counts_failures_and_skips_and_escapes_logs() {
+        let path = std::env::temp_dir().join(format!("report-test-{}.xml", std::process::id()));
+        write_junit(&path, Operation::Install, SystemTime::UNIX_EPOCH, &items()).unwrap();
+        let xml = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(xml.contains(
+            "<testsuites name=\"install_tools_tui\" tests=\"4\" failures=\"1\" skipped=\"1\" time=\"1.500\">"
+        ));
+        assert!(xml.contains(
+            "timestamp=\"1970-01-01T00:00:00Z\" tests=\"4\" failures=\"1\" skipped=\"1\""
+        ));
+        assert!(xml.contains("<testcase classname=\"brew\" name=\"b&amp;d\" time=\"1.500\">"));
+        assert!(xml.contains(
+            "<failure message=\"exit &lt;1&gt;\">$ brew install b&amp;d\nError: &quot;b&amp;d&quot; isn&apos;t[0m here</failure>"
+        ));
+        assert!(xml.contains("<skipped message=\"Cancelled\"/>"));
+        assert_eq!(xml.matches("<testcase ").count(), 4);
+        assert!(!xml.contains("unselected"));
+        assert!(!xml.contains('\u{1b}'));
+    }
+}
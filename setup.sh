@@ -200,8 +200,19 @@ build_and_run_installer() {
         return 1
     fi
 
-    log_info "Launching install_tools_tui..."
-    if ! BREWFILE_PATH="$DOTFILES_DIR/Brewfile" "$binary"; then
+    local args=()
+    if [ "$YES" -eq 1 ]; then
+        log_info "Installing every Brewfile entry with install_tools_tui (headless)..."
+        args=(--headless --all)
+    else
+        log_info "Launching install_tools_tui..."
+    fi
+    local status=0
+    BREWFILE_PATH="$DOTFILES_DIR/Brewfile" "$binary" ${args[@]+"${args[@]}"} || status=$?
+    if [ "$status" -eq 2 ]; then
+        log_error "Some selected tools failed to install."
+        return 1
+    elif [ "$status" -ne 0 ]; then
         log_error "Installer TUI exited with an error."
         return 1
     fi